@@ -0,0 +1,2 @@
+ALTER TABLE user
+DROP must_change_password
\ No newline at end of file
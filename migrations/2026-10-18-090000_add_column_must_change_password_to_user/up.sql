@@ -0,0 +1,2 @@
+ALTER TABLE user
+ADD must_change_password BOOLEAN NOT NULL DEFAULT 0
\ No newline at end of file
@@ -0,0 +1,22 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE balance_old (
+  id INTEGER PRIMARY KEY NOT NULL,
+  person_id INTEGER REFERENCES person(id) NOT NULL,
+  value FLOAT NOT NULL
+);
+INSERT INTO balance_old (id, person_id, value)
+SELECT id, person_id, value / 100.0 FROM balance;
+DROP TABLE balance;
+ALTER TABLE balance_old RENAME TO balance;
+
+CREATE TABLE movement_old (
+  id INTEGER PRIMARY KEY NOT NULL,
+  person_id INTEGER REFERENCES person(id) NOT NULL,
+  value FLOAT NOT NULL,
+  actor VARCHAR NOT NULL,
+  date DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO movement_old (id, person_id, value, actor, date)
+SELECT id, person_id, value / 100.0, actor, date FROM movement;
+DROP TABLE movement;
+ALTER TABLE movement_old RENAME TO movement;
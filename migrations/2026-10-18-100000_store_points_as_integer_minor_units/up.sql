@@ -0,0 +1,27 @@
+-- Points are stored as integer hundredths of a point instead of FLOAT.
+CREATE TABLE balance_new (
+  id INTEGER PRIMARY KEY NOT NULL,
+  person_id INTEGER REFERENCES person(id) NOT NULL,
+  value BIGINT NOT NULL
+);
+INSERT INTO balance_new (id, person_id, value)
+SELECT id, person_id, CAST(ROUND(value * 100) AS INTEGER) FROM balance;
+DROP TABLE balance;
+ALTER TABLE balance_new RENAME TO balance;
+
+CREATE TABLE movement_new (
+  id INTEGER PRIMARY KEY NOT NULL,
+  person_id INTEGER REFERENCES person(id) NOT NULL,
+  value BIGINT NOT NULL,
+  actor VARCHAR NOT NULL,
+  date DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO movement_new (id, person_id, value, actor, date)
+SELECT id, person_id, CAST(ROUND(value * 100) AS INTEGER), actor, date FROM movement;
+DROP TABLE movement;
+ALTER TABLE movement_new RENAME TO movement;
+
+-- Rebuild the cache from the converted movements so rounding drift is gone.
+UPDATE balance SET value = (
+  SELECT COALESCE(SUM(movement.value), 0) FROM movement WHERE movement.person_id = balance.person_id
+);
@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE movement_old (
+  id INTEGER PRIMARY KEY NOT NULL,
+  person_id INTEGER REFERENCES person(id) NOT NULL,
+  value BIGINT NOT NULL,
+  actor VARCHAR NOT NULL,
+  date DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO movement_old (id, person_id, value, actor, date)
+SELECT id, person_id, value, actor, date FROM movement;
+DROP TABLE movement;
+ALTER TABLE movement_old RENAME TO movement;
@@ -0,0 +1,13 @@
+ALTER TABLE movement ADD kind VARCHAR NOT NULL DEFAULT 'adjustment';
+ALTER TABLE movement ADD counterparty_person_id INTEGER REFERENCES person(id);
+ALTER TABLE movement ADD transfer_id VARCHAR;
+ALTER TABLE movement ADD memo VARCHAR;
+
+-- Best effort classification of the movements recorded before kinds existed.
+UPDATE movement SET kind = 'initial' WHERE actor = 'system';
+UPDATE movement
+SET kind = CASE WHEN value < 0 THEN 'revoke' ELSE 'grant' END
+WHERE kind = 'adjustment' AND actor IN (SELECT username FROM user WHERE superuser = 1);
+UPDATE movement
+SET kind = CASE WHEN value < 0 THEN 'transfer_out' ELSE 'transfer_in' END
+WHERE kind = 'adjustment' AND actor IN (SELECT username FROM user WHERE superuser = 0);
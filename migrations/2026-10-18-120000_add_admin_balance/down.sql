@@ -0,0 +1,2 @@
+DELETE FROM balance
+WHERE person_id = (SELECT person.id FROM person WHERE person.name = 'admin') AND value = 0;
@@ -0,0 +1,4 @@
+-- Every person must have exactly one balance row, including the seeded admin.
+INSERT INTO balance (person_id, value)
+SELECT person.id, 0 FROM person
+WHERE person.name = 'admin' AND person.id NOT IN (SELECT person_id FROM balance);
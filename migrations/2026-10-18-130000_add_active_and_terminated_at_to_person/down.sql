@@ -0,0 +1,4 @@
+ALTER TABLE person
+DROP terminated_at;
+ALTER TABLE person
+DROP active;
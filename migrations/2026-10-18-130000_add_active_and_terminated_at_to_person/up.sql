@@ -0,0 +1,4 @@
+ALTER TABLE person
+ADD active BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE person
+ADD terminated_at DATETIME;
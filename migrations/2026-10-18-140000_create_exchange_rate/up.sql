@@ -0,0 +1,9 @@
+CREATE TABLE exchange_rate (
+  id INTEGER PRIMARY KEY NOT NULL,
+  currency VARCHAR NOT NULL,
+  rate VARCHAR NOT NULL,
+  fetched_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  source VARCHAR NOT NULL
+);
+
+CREATE INDEX exchange_rate_currency_fetched_at ON exchange_rate (currency, fetched_at);
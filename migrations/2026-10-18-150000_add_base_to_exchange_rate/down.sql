@@ -0,0 +1,2 @@
+ALTER TABLE exchange_rate
+DROP base;
@@ -0,0 +1,2 @@
+ALTER TABLE exchange_rate
+ADD base VARCHAR NOT NULL DEFAULT 'USD';
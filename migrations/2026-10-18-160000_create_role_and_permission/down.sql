@@ -0,0 +1,12 @@
+ALTER TABLE user
+ADD superuser BOOLEAN NOT NULL DEFAULT 0;
+
+UPDATE user SET superuser = 1
+WHERE role = 'superadmin'
+   OR person_id IN (SELECT id FROM person WHERE lower(dept) = 'management');
+
+ALTER TABLE user
+DROP COLUMN role;
+
+DROP TABLE permission;
+DROP TABLE role;
@@ -0,0 +1,51 @@
+CREATE TABLE role (
+  name VARCHAR PRIMARY KEY NOT NULL,
+  description VARCHAR NOT NULL
+);
+
+CREATE TABLE permission (
+  id INTEGER PRIMARY KEY NOT NULL,
+  role VARCHAR NOT NULL REFERENCES role(name),
+  name VARCHAR NOT NULL,
+  UNIQUE (role, name)
+);
+
+INSERT INTO role (name, description) VALUES
+  ('viewer', 'Can only see its own balance and movements'),
+  ('employee', 'Can transfer points to other people'),
+  ('manager', 'Can see everyone''s balance and movements'),
+  ('hr_admin', 'Can load people and reset passwords'),
+  ('finance', 'Can add and remove points'),
+  ('superadmin', 'Can do everything');
+
+INSERT INTO permission (role, name) VALUES
+  ('employee', 'points.transfer'),
+  ('manager', 'points.transfer'),
+  ('manager', 'people.read'),
+  ('hr_admin', 'points.transfer'),
+  ('hr_admin', 'people.read'),
+  ('hr_admin', 'people.write'),
+  ('hr_admin', 'users.manage'),
+  ('finance', 'points.transfer'),
+  ('finance', 'people.read'),
+  ('finance', 'points.grant'),
+  ('superadmin', 'points.transfer'),
+  ('superadmin', 'people.read'),
+  ('superadmin', 'people.write'),
+  ('superadmin', 'points.grant'),
+  ('superadmin', 'users.manage'),
+  ('superadmin', 'roles.manage'),
+  ('superadmin', 'database.admin');
+
+ALTER TABLE user
+ADD role VARCHAR NOT NULL DEFAULT 'employee';
+
+-- The same mapping `load` uses for new people: Management gets the manager role. The
+-- other superusers, such as the seeded admin, become superadmins.
+UPDATE user SET role = 'manager'
+WHERE person_id IN (SELECT id FROM person WHERE lower(dept) = 'management');
+UPDATE user SET role = 'superadmin'
+WHERE superuser = 1 AND role = 'employee';
+
+ALTER TABLE user
+DROP COLUMN superuser;
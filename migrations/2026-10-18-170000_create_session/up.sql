@@ -0,0 +1,9 @@
+CREATE TABLE session (
+  id VARCHAR PRIMARY KEY NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES user(id),
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at DATETIME NOT NULL,
+  revoked_at DATETIME
+);
+
+CREATE INDEX session_user_id ON session (user_id);
@@ -0,0 +1,6 @@
+ALTER TABLE user
+DROP COLUMN locked_until;
+ALTER TABLE user
+DROP COLUMN failed_logins;
+
+DROP TABLE login_attempt;
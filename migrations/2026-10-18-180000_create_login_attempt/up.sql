@@ -0,0 +1,13 @@
+CREATE TABLE login_attempt (
+  id INTEGER PRIMARY KEY NOT NULL,
+  username VARCHAR NOT NULL,
+  attempted_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  success BOOLEAN NOT NULL
+);
+
+CREATE INDEX login_attempt_username_attempted_at ON login_attempt (username, attempted_at);
+
+ALTER TABLE user
+ADD failed_logins INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE user
+ADD locked_until DATETIME;
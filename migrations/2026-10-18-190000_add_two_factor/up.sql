@@ -0,0 +1,13 @@
+ALTER TABLE user
+ADD totp_secret VARCHAR;
+ALTER TABLE user
+ADD totp_enabled BOOLEAN NOT NULL DEFAULT 0;
+
+CREATE TABLE recovery_code (
+  id INTEGER PRIMARY KEY NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES user(id),
+  code_hash VARCHAR NOT NULL,
+  used_at DATETIME
+);
+
+CREATE INDEX recovery_code_user_id ON recovery_code (user_id);
@@ -0,0 +1,11 @@
+DROP TABLE api_key;
+
+DELETE FROM permission WHERE name = 'services.manage';
+DELETE FROM user WHERE service = 1;
+DELETE FROM role WHERE name = 'service';
+
+ALTER TABLE user
+DROP COLUMN service;
+
+DELETE FROM balance WHERE person_id IN (SELECT id FROM person WHERE email = 'system@system.com');
+DELETE FROM person WHERE email = 'system@system.com';
@@ -0,0 +1,26 @@
+-- Service accounts are users of this person, it holds no points of its own.
+INSERT INTO person (email, name, "role", dept, currency) VALUES ('system@system.com', 'system', 'system', 'system', 'USD');
+INSERT INTO balance (person_id, value) SELECT person.id, 0 FROM person WHERE person.email = 'system@system.com';
+
+ALTER TABLE user
+ADD service BOOLEAN NOT NULL DEFAULT 0;
+
+INSERT INTO role (name, description) VALUES
+  ('service', 'Service account, its API keys set what it can do');
+
+INSERT INTO permission (role, name) VALUES
+  ('superadmin', 'services.manage');
+
+CREATE TABLE api_key (
+  id VARCHAR PRIMARY KEY NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES user(id),
+  key_hash VARCHAR NOT NULL,
+  scopes VARCHAR NOT NULL,
+  max_points BIGINT,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at DATETIME NOT NULL,
+  last_used_at DATETIME,
+  revoked_at DATETIME
+);
+
+CREATE INDEX api_key_user_id ON api_key (user_id);
@@ -0,0 +1,4 @@
+UPDATE user SET totp_secret = totp_pending_secret WHERE totp_enabled = 0;
+
+ALTER TABLE user
+DROP COLUMN totp_pending_secret;
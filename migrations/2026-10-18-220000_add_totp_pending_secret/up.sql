@@ -0,0 +1,6 @@
+-- A secret from `enroll` waits here until `activate` confirms it, so that enrolling
+-- again does not turn off the secret in use.
+ALTER TABLE user
+ADD totp_pending_secret VARCHAR;
+
+UPDATE user SET totp_pending_secret = totp_secret, totp_secret = NULL WHERE totp_enabled = 0;
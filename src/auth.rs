@@ -1,17 +1,122 @@
+pub mod api_key;
+pub mod permission;
+pub mod session;
+pub mod two_factor;
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::Error as IoError;
+
+use argon2::password_hash::Error as PasswordHashError;
+use chrono::{Duration, Timelike, Utc};
 use diesel::result::{ConnectionError, Error};
-use passwords::hasher;
+use diesel::SqliteConnection;
 
+use crate::auth::api_key::{api_key_id, hash_api_key};
+use crate::auth::permission::Permission;
+use crate::auth::session::{read_token_file, remove_token_file, write_token_file, SessionToken};
+use crate::auth::two_factor::{
+    check_code, generate_recovery_codes, generate_secret, hash_recovery_code, otpauth_uri,
+};
+use crate::config;
 use crate::database;
-use crate::database::models::{Person, User};
-use crate::security::{generate_salt, verify_password};
+use crate::database::models::{NewSession, Person, Session, User};
+use crate::security::{decrypt, encrypt, hash_password, needs_rehash, verify_password};
+use crate::utils::points::format_points;
 
 #[derive(Debug)]
 pub enum AuthenticationError {
     DatabaseConnection(ConnectionError),
     Database(Error),
     UserNotAuthenticated,
-    AccessDenied,
+    AccessDenied(Permission),
+    PasswordChangeRequired,
+    TwoFactorRequired,
+    TwoFactorInvalid,
+    TwoFactorNotEnrolled,
+    UserInactive,
+    SessionInvalid,
+    SecretKeyMissing,
+    ApiKeyInvalid,
+    ApiKeyNotAllowed,
+    PointsCapExceeded(i64),
+    Io(IoError),
     Controller(database::controller::ControllerError),
+    KeyMaterial(PasswordHashError),
+}
+
+impl fmt::Display for AuthenticationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthenticationError::DatabaseConnection(error) => {
+                write!(f, "could not connect to the database: {}", error)
+            }
+            AuthenticationError::Database(Error::NotFound) => {
+                write!(f, "invalid username or password")
+            }
+            AuthenticationError::Database(error) => write!(f, "database error: {}", error),
+            AuthenticationError::UserNotAuthenticated => write!(f, "invalid username or password"),
+            AuthenticationError::AccessDenied(permission) => write!(
+                f,
+                "you are not allowed to run this command, it requires the {} permission",
+                permission
+            ),
+            AuthenticationError::PasswordChangeRequired => {
+                write!(f, "your password must be changed, run `passwd` first")
+            }
+            AuthenticationError::TwoFactorRequired => write!(
+                f,
+                "your role requires two-factor authentication, run `user 2fa enroll` first"
+            ),
+            AuthenticationError::TwoFactorInvalid => write!(f, "invalid two-factor code"),
+            AuthenticationError::TwoFactorNotEnrolled => {
+                write!(
+                    f,
+                    "no pending two-factor enrollment, run `user 2fa enroll` first"
+                )
+            }
+            AuthenticationError::UserInactive => write!(f, "this account is no longer active"),
+            AuthenticationError::SessionInvalid => {
+                write!(
+                    f,
+                    "your session is invalid or has expired, run `login` again"
+                )
+            }
+            AuthenticationError::SecretKeyMissing => {
+                write!(f, "DUNDIE_SECRET_KEY must be set to use sessions")
+            }
+            AuthenticationError::ApiKeyInvalid => {
+                write!(f, "the API key is invalid, revoked or expired")
+            }
+            AuthenticationError::ApiKeyNotAllowed => {
+                write!(f, "this command cannot be run with an API key")
+            }
+            AuthenticationError::PointsCapExceeded(cap) => write!(
+                f,
+                "the API key allows at most {} points per call",
+                format_points(*cap)
+            ),
+            AuthenticationError::Io(error) => {
+                write!(f, "could not access the session file: {}", error)
+            }
+            AuthenticationError::Controller(error) => write!(f, "{}", error),
+            AuthenticationError::KeyMaterial(error) => {
+                write!(f, "could not check the password: {}", error)
+            }
+        }
+    }
+}
+
+impl StdError for AuthenticationError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            AuthenticationError::DatabaseConnection(error) => Some(error),
+            AuthenticationError::Database(error) => Some(error),
+            AuthenticationError::Controller(error) => Some(error),
+            AuthenticationError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
 }
 
 impl From<ConnectionError> for AuthenticationError {
@@ -26,51 +131,696 @@ impl From<database::controller::ControllerError> for AuthenticationError {
     }
 }
 
-pub fn get_password_hash(password: &String) -> String {
-    let salt = generate_salt();
-    let hashed = hasher::bcrypt(10, &salt, password).unwrap();
+impl From<IoError> for AuthenticationError {
+    fn from(value: IoError) -> Self {
+        Self::Io(value)
+    }
+}
 
-    hashed.map(|x| x.to_string()).join("")
+impl From<PasswordHashError> for AuthenticationError {
+    fn from(value: PasswordHashError) -> Self {
+        Self::KeyMaterial(value)
+    }
+}
+
+/// A hash of a throwaway password, checked against when the username does not exist.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$HPktPHuBmMUifdYsV4mONw$j+WtF8v3MWyO0dEon8pCcCLu5oV2ETu6qgm86WaSZBg";
+
+pub fn get_password_hash(password: &str) -> Result<String, AuthenticationError> {
+    Ok(hash_password(password)?)
 }
 
+/// Checks the credentials of `username` and returns its person and user rows.
+///
+/// Unknown usernames, wrong passwords and locked users all get the same error, so the
+/// response does not tell which usernames exist. Every attempt is recorded and a user
+/// is locked after too many failures in a row, see [`config::lockout_policy`].
+///
+/// Users with two-factor authentication are asked for a code through `second_factor`,
+/// which accepts a TOTP code or one of their recovery codes.
 pub fn authenticate_user(
     username: &String,
-    password: &String,
-    requires_superuser: bool,
+    password: &str,
+    second_factor: impl FnOnce() -> String,
+    permission: Option<Permission>,
+    credential_setup: bool,
 ) -> Result<(Person, User), AuthenticationError> {
     let mut connection = database::connection::establish_connection()?;
+    let now = Utc::now().naive_utc();
 
-    match database::controller::user_exists(&mut connection, username) {
-        Some(user) => {
-            let verified_user = verify_password(password, &user.password);
-
-            if requires_superuser {
-                if user.superuser {
-                    if verified_user {
-                        Ok((
-                            database::controller::query_person_by_id(
-                                &mut connection,
-                                user.person_id,
-                            )?,
-                            user,
-                        ))
-                    } else {
-                        Err(AuthenticationError::UserNotAuthenticated)
-                    }
-                } else {
-                    Err(AuthenticationError::AccessDenied)
-                }
-            } else {
-                if verified_user {
-                    Ok((
-                        database::controller::query_person_by_id(&mut connection, user.person_id)?,
-                        user,
-                    ))
-                } else {
-                    Err(AuthenticationError::UserNotAuthenticated)
-                }
-            }
+    // Service accounts only act through API keys.
+    let Some(user) =
+        database::controller::user_exists(&mut connection, username).filter(|user| !user.service)
+    else {
+        // Hash anyway so that unknown usernames take as long as known ones.
+        let _ = verify_password(password, DUMMY_PASSWORD_HASH);
+        database::controller::add_login_attempt(&mut connection, username, false, now)?;
+        return Err(AuthenticationError::UserNotAuthenticated);
+    };
+
+    if user
+        .locked_until
+        .is_some_and(|locked_until| locked_until > now)
+    {
+        // Same work as for unknown usernames, so a lock does not show in the timing.
+        let _ = verify_password(password, DUMMY_PASSWORD_HASH);
+        database::controller::add_login_attempt(&mut connection, username, false, now)?;
+        return Err(AuthenticationError::UserNotAuthenticated);
+    }
+
+    if !verify_password(password, &user.password)? {
+        register_failed_login(&mut connection, &user, now)?;
+        return Err(AuthenticationError::UserNotAuthenticated);
+    }
+
+    if user.totp_enabled && !check_second_factor(&mut connection, &user, &second_factor(), now)? {
+        register_failed_login(&mut connection, &user, now)?;
+        return Err(AuthenticationError::TwoFactorInvalid);
+    }
+
+    database::controller::add_login_attempt(&mut connection, username, true, now)?;
+    let user = if user.failed_logins > 0 || user.locked_until.is_some() {
+        database::controller::unlock_user(&mut connection, &user)?
+    } else {
+        user
+    };
+
+    if needs_rehash(&user.password) {
+        database::controller::update_password(
+            &mut connection,
+            &user,
+            &get_password_hash(password)?,
+        )?;
+    }
+
+    authorize_user(&mut connection, user, permission, credential_setup)
+}
+
+fn register_failed_login(
+    connection: &mut SqliteConnection,
+    user: &User,
+    now: chrono::NaiveDateTime,
+) -> Result<(), AuthenticationError> {
+    let policy = config::lockout_policy();
+    database::controller::add_login_attempt(connection, &user.username, false, now)?;
+    database::controller::register_failed_login(
+        connection,
+        user,
+        policy.max_failures,
+        Duration::minutes(policy.minutes),
+        now,
+    )?;
+    Ok(())
+}
+
+fn check_second_factor(
+    connection: &mut SqliteConnection,
+    user: &User,
+    code: &str,
+    now: chrono::NaiveDateTime,
+) -> Result<bool, AuthenticationError> {
+    // A secret that cannot be read (key missing or rotated) must not lock the user out
+    // of their recovery codes, so it only counts as a code that did not match.
+    let step = totp_secret(user)
+        .ok()
+        .and_then(|secret| check_code(&secret, &user.username, code, now.timestamp()));
+    if let Some(step) = step {
+        if database::controller::use_totp_step(connection, user, step)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(database::controller::use_recovery_code(
+        connection,
+        user,
+        &hash_recovery_code(code),
+        now,
+    )?)
+}
+
+/// The decrypted TOTP secret the user logs in with.
+fn totp_secret(user: &User) -> Result<Vec<u8>, AuthenticationError> {
+    decrypt_totp_secret(user.totp_secret.as_deref())
+}
+
+fn decrypt_totp_secret(encrypted: Option<&str>) -> Result<Vec<u8>, AuthenticationError> {
+    let secret_key = secret_key()?;
+
+    encrypted
+        .and_then(|encrypted| decrypt(&secret_key, encrypted))
+        .ok_or(AuthenticationError::TwoFactorNotEnrolled)
+}
+
+fn secret_key() -> Result<String, AuthenticationError> {
+    let secret_key = config::session_settings().secret_key;
+    if secret_key.is_empty() {
+        return Err(AuthenticationError::SecretKeyMissing);
+    }
+    Ok(secret_key)
+}
+
+/// Accepts a token issued by [`login`] in place of the credentials.
+pub fn authenticate_session(
+    token: &str,
+    permission: Option<Permission>,
+    credential_setup: bool,
+) -> Result<(Person, User), AuthenticationError> {
+    let secret_key = secret_key()?;
+
+    let now = Utc::now().naive_utc();
+    let session_token = match SessionToken::decode(&secret_key, token) {
+        Some(session_token) if session_token.expires_at > now => session_token,
+        _ => return Err(AuthenticationError::SessionInvalid),
+    };
+
+    let mut connection = database::connection::establish_connection()?;
+    let user = session_user(&mut connection, &session_token.id, now)?;
+
+    authorize_user(&mut connection, user, permission, credential_setup)
+}
+
+/// The user of the active session `id`. Users locked after failed logins are refused, as
+/// [`authenticate_user`] refuses them, until the lock expires.
+fn session_user(
+    connection: &mut SqliteConnection,
+    id: &str,
+    now: chrono::NaiveDateTime,
+) -> Result<User, AuthenticationError> {
+    let Some(session) = database::controller::query_active_session(connection, id, now)? else {
+        return Err(AuthenticationError::SessionInvalid);
+    };
+    let user = database::controller::query_user_by_id(connection, session.user_id)?;
+
+    if user
+        .locked_until
+        .is_some_and(|locked_until| locked_until > now)
+    {
+        return Err(AuthenticationError::SessionInvalid);
+    }
+
+    Ok(user)
+}
+
+/// Accepts an API key of a service account in place of the credentials.
+///
+/// Keys only run commands that require a permission, and that permission must be one of
+/// the scopes of the key. `points` is the amount the command moves in total, over every
+/// person it reaches, checked against the cap of the key when it has one.
+pub fn authenticate_api_key(
+    connection: &mut SqliteConnection,
+    key: &str,
+    permission: Option<Permission>,
+    points: Option<i64>,
+) -> Result<(Person, User), AuthenticationError> {
+    let id = api_key_id(key).ok_or(AuthenticationError::ApiKeyInvalid)?;
+    let now = Utc::now().naive_utc();
+
+    let api_key = match database::controller::query_api_key(connection, id)? {
+        Some(api_key)
+            if api_key.key_hash == hash_api_key(key)
+                && api_key.revoked_at.is_none()
+                && api_key.expires_at > now =>
+        {
+            api_key
         }
-        None => Err(AuthenticationError::Database(Error::NotFound)),
+        _ => return Err(AuthenticationError::ApiKeyInvalid),
+    };
+
+    let permission = permission.ok_or(AuthenticationError::ApiKeyNotAllowed)?;
+    if !api_key
+        .scopes
+        .split(',')
+        .any(|scope| scope == permission.as_str())
+    {
+        return Err(AuthenticationError::AccessDenied(permission));
+    }
+
+    if let (Some(cap), Some(points)) = (api_key.max_points, points) {
+        if points.abs() > cap {
+            return Err(AuthenticationError::PointsCapExceeded(cap));
+        }
+    }
+
+    let api_key = database::controller::touch_api_key(connection, &api_key, now)?;
+    let user = database::controller::query_user_by_id(connection, api_key.user_id)?;
+    let person = database::controller::query_person_by_id(connection, user.person_id)?;
+
+    if !person.active {
+        return Err(AuthenticationError::UserInactive);
+    }
+
+    Ok((person, user))
+}
+
+/// Checks the credentials of a database administrator while migrations are pending,
+/// when the current user model cannot be loaded yet. Only the password and the
+/// `database.admin` permission are checked, lockouts and two-factor authentication rely
+/// on columns the schema may not have yet.
+pub fn authenticate_schema_admin(
+    connection: &mut SqliteConnection,
+    username: &str,
+    password: &str,
+) -> Result<(), AuthenticationError> {
+    let permission = Permission::DatabaseAdmin;
+    let Some(admin) =
+        database::controller::query_schema_admin(connection, username, permission.as_str())?
+    else {
+        let _ = verify_password(password, DUMMY_PASSWORD_HASH);
+        return Err(AuthenticationError::UserNotAuthenticated);
+    };
+
+    if !verify_password(password, &admin.password)? {
+        return Err(AuthenticationError::UserNotAuthenticated);
+    }
+    if !admin.granted {
+        return Err(AuthenticationError::AccessDenied(permission));
+    }
+
+    Ok(())
+}
+
+/// Checks that an authenticated user may go on.
+///
+/// When `permission` is set the role of the user must hold it. Users flagged with
+/// `must_change_password` are only let through when `credential_setup` is set, i.e. when
+/// the caller is about to change the password.
+fn authorize_user(
+    connection: &mut SqliteConnection,
+    user: User,
+    permission: Option<Permission>,
+    credential_setup: bool,
+) -> Result<(Person, User), AuthenticationError> {
+    let person = database::controller::query_person_by_id(connection, user.person_id)?;
+
+    if !person.active {
+        return Err(AuthenticationError::UserInactive);
+    }
+
+    if let Some(permission) = permission {
+        if !database::controller::role_has_permission(connection, &user.role, permission.as_str())?
+        {
+            return Err(AuthenticationError::AccessDenied(permission));
+        }
+    }
+
+    if user.must_change_password && !credential_setup {
+        return Err(AuthenticationError::PasswordChangeRequired);
+    }
+
+    if !user.totp_enabled
+        && !credential_setup
+        && config::two_factor_required_roles().contains(&user.role)
+    {
+        return Err(AuthenticationError::TwoFactorRequired);
+    }
+
+    Ok((person, user))
+}
+
+/// Whether the role of `user` holds `permission`, for commands that show more to users
+/// who have it.
+pub fn has_permission(user: &User, permission: Permission) -> Result<bool, AuthenticationError> {
+    let mut connection = database::connection::establish_connection()?;
+
+    Ok(database::controller::role_has_permission(
+        &mut connection,
+        &user.role,
+        permission.as_str(),
+    )?)
+}
+
+/// Opens a session for `user` and stores its token in the session file.
+pub fn login(user: &User) -> Result<Session, AuthenticationError> {
+    let settings = config::session_settings();
+    let secret_key = secret_key()?;
+
+    // Tokens carry the expiry in whole seconds, the row should match it.
+    let now = Utc::now().naive_utc().with_nanosecond(0).unwrap();
+    let session_token = SessionToken::new(now + Duration::seconds(settings.ttl));
+
+    let mut connection = database::connection::establish_connection()?;
+    revoke_session_in_file(&mut connection, &settings.file)?;
+    let session = database::controller::add_session(
+        &mut connection,
+        &NewSession {
+            id: session_token.id.clone(),
+            user_id: user.id,
+            created_at: now,
+            expires_at: session_token.expires_at,
+        },
+    )?;
+    write_token_file(&settings.file, &session_token.encode(&secret_key))?;
+
+    Ok(session)
+}
+
+/// Revokes the session in the session file, if any, and removes the file. Returns the
+/// revoked session.
+pub fn logout() -> Result<Option<Session>, AuthenticationError> {
+    let settings = config::session_settings();
+    let mut connection = database::connection::establish_connection()?;
+    let revoked = revoke_session_in_file(&mut connection, &settings.file)?;
+
+    remove_token_file(&settings.file)?;
+    Ok(revoked)
+}
+
+/// Revokes the session named by the token in `file`. The signature is not checked, so
+/// that a session can still be closed when the secret key is missing or was changed.
+fn revoke_session_in_file(
+    connection: &mut SqliteConnection,
+    file: &str,
+) -> Result<Option<Session>, AuthenticationError> {
+    let Some(token) = read_token_file(file) else {
+        return Ok(None);
+    };
+    let Some(id) = SessionToken::unverified_id(&token) else {
+        return Ok(None);
+    };
+
+    match database::controller::revoke_session(connection, id) {
+        Ok(session) => Ok(Some(session)),
+        Err(database::controller::ControllerError::SessionNotFound(_)) => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Starts enrolling `user` in two-factor authentication and returns the `otpauth://`
+/// URI for an authenticator app. Enrolling again replaces a previous secret once the new
+/// one is activated, until then the previous one keeps working.
+pub fn enroll_two_factor(user: &User) -> Result<String, AuthenticationError> {
+    let secret = generate_secret();
+    let mut connection = database::connection::establish_connection()?;
+    database::controller::set_totp_secret(
+        &mut connection,
+        user,
+        &encrypt(&secret_key()?, &secret),
+    )?;
+
+    Ok(otpauth_uri(&secret, &user.username))
+}
+
+/// Turns two-factor authentication on once the user proves the app has the secret, and
+/// returns a fresh set of single-use recovery codes.
+pub fn activate_two_factor(user: &User, code: &str) -> Result<Vec<String>, AuthenticationError> {
+    let now = Utc::now().timestamp();
+    let secret = decrypt_totp_secret(user.totp_pending_secret.as_deref())?;
+    let Some(step) = check_code(&secret, &user.username, code, now) else {
+        return Err(AuthenticationError::TwoFactorInvalid);
+    };
+
+    let recovery_codes = generate_recovery_codes();
+    let code_hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| hash_recovery_code(code))
+        .collect();
+
+    let mut connection = database::connection::establish_connection()?;
+    database::controller::enable_totp(&mut connection, user, &code_hashes, step)?;
+
+    Ok(recovery_codes)
+}
+
+pub fn disable_two_factor(user: &User) -> Result<User, AuthenticationError> {
+    let mut connection = database::connection::establish_connection()?;
+    Ok(database::controller::disable_totp(&mut connection, user)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use diesel::SqliteConnection;
+    use rstest::rstest;
+
+    use crate::auth::api_key::{api_key_id, generate_api_key, hash_api_key};
+    use crate::auth::permission::Permission;
+    use crate::auth::{
+        authenticate_api_key, authenticate_schema_admin, authorize_user, session_user,
+        AuthenticationError,
+    };
+    use crate::database::controller::test::{new_person, test_db_connection};
+    use crate::database::controller::{
+        add_api_key, add_person, add_service_account, add_session, deactivate_person,
+        query_api_key, query_person_by_id, query_user_by_person, register_failed_login,
+        revoke_api_key, set_role, unlock_user, update_password,
+    };
+    use crate::database::migrations::{has_table, migration_status, revert_migrations};
+    use crate::database::models::{NewApiKey, NewPerson, NewSession};
+    use crate::security::hash_password;
+    use crate::utils::date::{parse_since, parse_until};
+
+    /// Issues a `points.grant` key capped at 100.00 points for a new service account.
+    fn issue_key(connection: &mut SqliteConnection, expires: &str) -> String {
+        let service_account = add_service_account(connection, "payroll").unwrap();
+        let generated = generate_api_key();
+        add_api_key(
+            connection,
+            &NewApiKey {
+                id: generated.id,
+                user_id: service_account.id,
+                key_hash: hash_api_key(&generated.key),
+                scopes: "points.grant".to_string(),
+                max_points: Some(10000),
+                created_at: parse_since("2026-10-18T00:00:00").unwrap(),
+                expires_at: parse_until(expires).unwrap(),
+            },
+        )
+        .unwrap();
+
+        generated.key
+    }
+
+    #[rstest]
+    fn api_key_authenticates_its_service_account(mut test_db_connection: SqliteConnection) {
+        let key = issue_key(&mut test_db_connection, "2099-12-31");
+
+        let (person, user) = authenticate_api_key(
+            &mut test_db_connection,
+            &key,
+            Some(Permission::PointsGrant),
+            Some(10000),
+        )
+        .unwrap();
+        assert_eq!(user.username, "payroll");
+        assert!(person.is_internal());
+
+        let id = api_key_id(&key).unwrap();
+        let api_key = query_api_key(&mut test_db_connection, id).unwrap().unwrap();
+        assert!(api_key.last_used_at.is_some());
+    }
+
+    #[rstest]
+    fn api_key_with_a_wrong_secret_is_invalid(mut test_db_connection: SqliteConnection) {
+        let key = issue_key(&mut test_db_connection, "2099-12-31");
+        let forged = format!("{}0", key);
+
+        let result = authenticate_api_key(
+            &mut test_db_connection,
+            &forged,
+            Some(Permission::PointsGrant),
+            None,
+        );
+        assert!(matches!(result, Err(AuthenticationError::ApiKeyInvalid)));
+    }
+
+    #[rstest]
+    fn revoked_api_key_is_invalid(mut test_db_connection: SqliteConnection) {
+        let key = issue_key(&mut test_db_connection, "2099-12-31");
+        revoke_api_key(&mut test_db_connection, api_key_id(&key).unwrap()).unwrap();
+
+        let result = authenticate_api_key(
+            &mut test_db_connection,
+            &key,
+            Some(Permission::PointsGrant),
+            None,
+        );
+        assert!(matches!(result, Err(AuthenticationError::ApiKeyInvalid)));
+    }
+
+    #[rstest]
+    fn expired_api_key_is_invalid(mut test_db_connection: SqliteConnection) {
+        let yesterday = (Utc::now() - chrono::Duration::days(1)).format("%Y-%m-%d");
+        let key = issue_key(&mut test_db_connection, &yesterday.to_string());
+
+        let result = authenticate_api_key(
+            &mut test_db_connection,
+            &key,
+            Some(Permission::PointsGrant),
+            None,
+        );
+        assert!(matches!(result, Err(AuthenticationError::ApiKeyInvalid)));
+    }
+
+    #[rstest]
+    fn api_key_needs_the_scope(mut test_db_connection: SqliteConnection) {
+        let key = issue_key(&mut test_db_connection, "2099-12-31");
+
+        let result = authenticate_api_key(
+            &mut test_db_connection,
+            &key,
+            Some(Permission::PointsTransfer),
+            Some(100),
+        );
+        assert!(matches!(
+            result,
+            Err(AuthenticationError::AccessDenied(
+                Permission::PointsTransfer
+            ))
+        ));
+    }
+
+    #[rstest]
+    fn api_key_cannot_run_commands_without_a_permission(mut test_db_connection: SqliteConnection) {
+        let key = issue_key(&mut test_db_connection, "2099-12-31");
+
+        let result = authenticate_api_key(&mut test_db_connection, &key, None, None);
+        assert!(matches!(result, Err(AuthenticationError::ApiKeyNotAllowed)));
+    }
+
+    #[rstest]
+    #[case(10001)]
+    #[case(-10001)]
+    fn api_key_points_are_capped(mut test_db_connection: SqliteConnection, #[case] points: i64) {
+        let key = issue_key(&mut test_db_connection, "2099-12-31");
+
+        let result = authenticate_api_key(
+            &mut test_db_connection,
+            &key,
+            Some(Permission::PointsGrant),
+            Some(points),
+        );
+        assert!(matches!(
+            result,
+            Err(AuthenticationError::PointsCapExceeded(10000))
+        ));
+    }
+
+    #[rstest]
+    fn api_key_of_an_inactive_person_is_refused(mut test_db_connection: SqliteConnection) {
+        let key = issue_key(&mut test_db_connection, "2099-12-31");
+        let (_, user) = authenticate_api_key(
+            &mut test_db_connection,
+            &key,
+            Some(Permission::PointsGrant),
+            None,
+        )
+        .unwrap();
+        let person = query_person_by_id(&mut test_db_connection, user.person_id).unwrap();
+        deactivate_person(&mut test_db_connection, &person).unwrap();
+
+        let result = authenticate_api_key(
+            &mut test_db_connection,
+            &key,
+            Some(Permission::PointsGrant),
+            None,
+        );
+        assert!(matches!(result, Err(AuthenticationError::UserInactive)));
+    }
+
+    #[rstest]
+    fn sessions_of_locked_users_are_refused(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        let now = Utc::now().naive_utc();
+        add_session(
+            &mut test_db_connection,
+            &NewSession {
+                id: "session-id".to_string(),
+                user_id: user.id,
+                created_at: now,
+                expires_at: now + Duration::hours(1),
+            },
+        )
+        .unwrap();
+        assert!(session_user(&mut test_db_connection, "session-id", now).is_ok());
+
+        let user = register_failed_login(
+            &mut test_db_connection,
+            &user,
+            1,
+            Duration::minutes(15),
+            now,
+        )
+        .unwrap();
+        let result = session_user(&mut test_db_connection, "session-id", now);
+        assert!(matches!(result, Err(AuthenticationError::SessionInvalid)));
+
+        // The session works again once the lock has expired or been cleared.
+        let later = now + Duration::minutes(16);
+        assert!(session_user(&mut test_db_connection, "session-id", later).is_ok());
+        unlock_user(&mut test_db_connection, &user).unwrap();
+        assert!(session_user(&mut test_db_connection, "session-id", now).is_ok());
+    }
+
+    /// Adds a person whose user logs in with `employee-pass` and holds no admin rights.
+    fn add_employee(connection: &mut SqliteConnection, new_person: &NewPerson) {
+        let (person, _) = add_person(connection, new_person).unwrap();
+        let user = query_user_by_person(connection, &person).unwrap();
+        update_password(connection, &user, &hash_password("employee-pass").unwrap()).unwrap();
+    }
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn schema_admin_needs_the_password_and_the_permission(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+        #[case] before_roles: bool,
+    ) {
+        add_employee(&mut test_db_connection, &new_person);
+        if before_roles {
+            // Back to the schema where a superuser flag stood in for permissions.
+            let since_roles = migration_status(&mut test_db_connection)
+                .unwrap()
+                .iter()
+                .filter(|(name, _)| name.as_str() >= "2026-10-18-160000")
+                .count();
+            revert_migrations(&mut test_db_connection, since_roles).unwrap();
+            assert!(!has_table(&mut test_db_connection, "permission").unwrap());
+        }
+
+        assert!(authenticate_schema_admin(&mut test_db_connection, "admin", "admin").is_ok());
+        assert!(matches!(
+            authenticate_schema_admin(&mut test_db_connection, "admin", "wrong"),
+            Err(AuthenticationError::UserNotAuthenticated)
+        ));
+        assert!(matches!(
+            authenticate_schema_admin(&mut test_db_connection, "nobody", "admin"),
+            Err(AuthenticationError::UserNotAuthenticated)
+        ));
+        assert!(matches!(
+            authenticate_schema_admin(&mut test_db_connection, "john-doe", "employee-pass"),
+            Err(AuthenticationError::AccessDenied(Permission::DatabaseAdmin))
+        ));
+    }
+
+    #[rstest]
+    fn admin_actions_wait_for_the_password_change(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        let user = set_role(&mut test_db_connection, &user, "hr_admin").unwrap();
+        assert!(user.must_change_password);
+
+        // `user 2fa disable --email` needs users.manage and is no credential setup.
+        let result = authorize_user(
+            &mut test_db_connection,
+            user.clone(),
+            Some(Permission::UsersManage),
+            false,
+        );
+        assert!(matches!(
+            result,
+            Err(AuthenticationError::PasswordChangeRequired)
+        ));
+
+        // `user 2fa disable` for themselves is.
+        assert!(authorize_user(&mut test_db_connection, user, None, true).is_ok());
     }
 }
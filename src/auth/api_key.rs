@@ -0,0 +1,61 @@
+use crate::security::hash_token;
+
+const PREFIX: &str = "dk";
+
+/// An API key is `dk_<id>_<secret>`. The id names the `api_key` row, only a hash of the
+/// whole key is stored.
+pub struct GeneratedApiKey {
+    pub id: String,
+    pub key: String,
+}
+
+pub fn generate_api_key() -> GeneratedApiKey {
+    let id = hex::encode(rand::random::<[u8; 4]>());
+    let secret = hex::encode(rand::random::<[u8; 16]>());
+
+    GeneratedApiKey {
+        key: format!("{}_{}_{}", PREFIX, id, secret),
+        id,
+    }
+}
+
+/// The id part of a well formed key.
+pub fn api_key_id(key: &str) -> Option<&str> {
+    let mut parts = key.trim().splitn(3, '_');
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(PREFIX), Some(id), Some(secret)) if !id.is_empty() && !secret.is_empty() => Some(id),
+        _ => None,
+    }
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    hash_token(key.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::auth::api_key::{api_key_id, generate_api_key, hash_api_key};
+
+    #[rstest]
+    fn generated_key_carries_its_id() {
+        let generated = generate_api_key();
+
+        assert_eq!(api_key_id(&generated.key), Some(generated.id.as_str()));
+        assert_eq!(
+            hash_api_key(&generated.key),
+            hash_api_key(&format!(" {}\n", generated.key))
+        );
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("dk_")]
+    #[case("dk_abc")]
+    #[case("xx_abc_def")]
+    fn malformed_key(#[case] key: &str) {
+        assert_eq!(api_key_id(key), None);
+    }
+}
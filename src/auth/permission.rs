@@ -0,0 +1,76 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// What a role is allowed to do. Each one is stored by name in the `permission` table,
+/// next to the role that holds it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    PeopleRead,
+    PeopleWrite,
+    PointsGrant,
+    PointsTransfer,
+    UsersManage,
+    RolesManage,
+    ServicesManage,
+    DatabaseAdmin,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 8] = [
+        Permission::PeopleRead,
+        Permission::PeopleWrite,
+        Permission::PointsGrant,
+        Permission::PointsTransfer,
+        Permission::UsersManage,
+        Permission::RolesManage,
+        Permission::ServicesManage,
+        Permission::DatabaseAdmin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::PeopleRead => "people.read",
+            Permission::PeopleWrite => "people.write",
+            Permission::PointsGrant => "points.grant",
+            Permission::PointsTransfer => "points.transfer",
+            Permission::UsersManage => "users.manage",
+            Permission::RolesManage => "roles.manage",
+            Permission::ServicesManage => "services.manage",
+            Permission::DatabaseAdmin => "database.admin",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Permission::ALL
+            .into_iter()
+            .find(|permission| permission.as_str() == value.trim())
+            .ok_or_else(|| format!("unknown permission {}", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::rstest;
+
+    use crate::auth::permission::Permission;
+
+    #[rstest]
+    fn permission_names_round_trip() {
+        for permission in Permission::ALL {
+            assert_eq!(Permission::from_str(permission.as_str()), Ok(permission));
+        }
+        assert!(Permission::from_str("points:grant").is_err());
+    }
+}
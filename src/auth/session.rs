@@ -0,0 +1,123 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Error as IoError, ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+use chrono::NaiveDateTime;
+
+use crate::security::{sign, verify_signature};
+
+/// A session token is `<session id>.<expiry as unix seconds>.<signature>`, signed with
+/// `DUNDIE_SECRET_KEY`. The session row is still checked, the signature only keeps
+/// forged or edited tokens from reaching the database.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SessionToken {
+    pub id: String,
+    pub expires_at: NaiveDateTime,
+}
+
+impl SessionToken {
+    pub fn new(expires_at: NaiveDateTime) -> Self {
+        SessionToken {
+            id: hex::encode(rand::random::<[u8; 16]>()),
+            expires_at,
+        }
+    }
+
+    fn payload(&self) -> String {
+        format!("{}.{}", self.id, self.expires_at.timestamp())
+    }
+
+    pub fn encode(&self, secret: &str) -> String {
+        let payload = self.payload();
+        format!("{}.{}", payload, sign(secret, &payload))
+    }
+
+    /// The session id of a token, without checking its signature. Only good enough to
+    /// revoke the session the token names.
+    pub fn unverified_id(token: &str) -> Option<&str> {
+        match token.trim().split_once('.') {
+            Some((id, _)) if !id.is_empty() => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Parses a token and checks its signature. Expiry is left to the caller.
+    pub fn decode(secret: &str, token: &str) -> Option<SessionToken> {
+        let (payload, signature) = token.trim().rsplit_once('.')?;
+        if !verify_signature(secret, payload, signature) {
+            return None;
+        }
+
+        let (id, expires_at) = payload.split_once('.')?;
+        let expires_at = NaiveDateTime::from_timestamp_opt(expires_at.parse().ok()?, 0)?;
+
+        Some(SessionToken {
+            id: id.to_string(),
+            expires_at,
+        })
+    }
+}
+
+pub fn read_token_file(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok()
+}
+
+/// Writes the token readable by the current OS user only, replacing an older one.
+pub fn write_token_file(path: &str, token: &str) -> Result<(), IoError> {
+    remove_token_file(path)?;
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    // Elsewhere the file gets the default permissions of its directory.
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(path)?;
+    file.write_all(token.as_bytes())
+}
+
+pub fn remove_token_file(path: &str) -> Result<(), IoError> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rstest::rstest;
+
+    use crate::auth::session::SessionToken;
+
+    #[rstest]
+    fn token_round_trip() {
+        let expires_at = NaiveDate::from_ymd_opt(2026, 10, 18)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap();
+        let token = SessionToken::new(expires_at);
+        let encoded = token.encode("secret");
+
+        assert_eq!(SessionToken::decode("secret", &encoded), Some(token));
+        assert_eq!(SessionToken::decode("other", &encoded), None);
+    }
+
+    #[rstest]
+    fn tampered_token_is_rejected() {
+        let expires_at = NaiveDate::from_ymd_opt(2026, 10, 18)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap();
+        let encoded = SessionToken::new(expires_at).encode("secret");
+        let (id, rest) = encoded.split_once('.').unwrap();
+        let (_, signature) = rest.split_once('.').unwrap();
+        let extended = format!("{}.{}.{}", id, 4102444800_i64, signature);
+
+        assert_eq!(SessionToken::decode("secret", &extended), None);
+        assert_eq!(SessionToken::decode("secret", "garbage"), None);
+        assert_eq!(SessionToken::unverified_id(&extended), Some(id));
+        assert_eq!(SessionToken::unverified_id("garbage"), None);
+    }
+}
@@ -0,0 +1,122 @@
+use totp_rs::{Algorithm, TOTP};
+
+use crate::security::hash_token;
+
+const ISSUER: &str = "Dundie Rewards";
+const SECRET_SIZE: usize = 20;
+const STEP: u64 = 30;
+const SKEW: i64 = 1;
+pub const RECOVERY_CODES: usize = 10;
+
+fn totp(secret: &[u8], username: &str) -> TOTP {
+    // RFC 6238 defaults, the ones authenticator apps expect. Clock skew is handled by
+    // `check_code`, which needs to know the step a code matched.
+    TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        0,
+        STEP,
+        secret.to_vec(),
+        Some(ISSUER.to_string()),
+        username.to_string(),
+    )
+}
+
+pub fn generate_secret() -> Vec<u8> {
+    rand::random::<[u8; SECRET_SIZE]>().to_vec()
+}
+
+/// The `otpauth://` URI to add the account to an authenticator app.
+pub fn otpauth_uri(secret: &[u8], username: &str) -> String {
+    totp(secret, username).get_url()
+}
+
+/// Checks a code for the time step of `now`, a Unix timestamp, accepting one step of
+/// clock skew. Returns the step the code belongs to, so that it can be used only once.
+pub fn check_code(secret: &[u8], username: &str, code: &str, now: i64) -> Option<i64> {
+    let totp = totp(secret, username);
+    let current = now / STEP as i64;
+
+    (current - SKEW..=current + SKEW)
+        .filter(|step| *step >= 0)
+        .find(|step| totp.check(code.trim(), *step as u64 * STEP))
+}
+
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let code = hex::encode(rand::random::<[u8; 5]>());
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// Recovery codes are stored hashed and compared without dashes or case.
+pub fn hash_recovery_code(code: &str) -> String {
+    hash_token(&code.trim().replace('-', "").to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use totp_rs::{Algorithm, TOTP};
+
+    use crate::auth::two_factor::{
+        check_code, generate_recovery_codes, generate_secret, hash_recovery_code, otpauth_uri,
+        RECOVERY_CODES,
+    };
+
+    #[rstest]
+    fn current_code_is_accepted() {
+        let secret = generate_secret();
+        let now = 1_792_324_815;
+        let code = TOTP::new_unchecked(
+            Algorithm::SHA1,
+            6,
+            1,
+            30,
+            secret.clone(),
+            None,
+            String::new(),
+        )
+        .generate(now as u64);
+
+        assert_eq!(
+            check_code(&secret, "jim-halpert", &code, now),
+            Some(now / 30)
+        );
+        assert_eq!(
+            check_code(&secret, "jim-halpert", &code, now + 30),
+            Some(now / 30)
+        );
+        assert_eq!(
+            check_code(&secret, "jim-halpert", &code, now - 30),
+            Some(now / 30)
+        );
+        assert_eq!(check_code(&secret, "jim-halpert", &code, now + 60), None);
+        assert_eq!(
+            check_code(&generate_secret(), "jim-halpert", &code, now),
+            None
+        );
+    }
+
+    #[rstest]
+    fn otpauth_uri_names_the_account() {
+        let uri = otpauth_uri(&generate_secret(), "jim-halpert");
+
+        assert!(uri.starts_with("otpauth://totp/Dundie%20Rewards:jim-halpert?secret="));
+        assert!(uri.contains("issuer=Dundie%20Rewards"));
+    }
+
+    #[rstest]
+    fn recovery_codes_are_unique_and_normalized() {
+        let codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODES);
+        assert_ne!(codes[0], codes[1]);
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&codes[0].replace('-', "").to_uppercase())
+        );
+    }
+}
@@ -1,13 +1,34 @@
 pub mod commands;
 
 use std::env;
+use std::error::Error as StdError;
+use std::fmt;
 use std::io::{self, Write};
+use std::str::FromStr;
 
+use chrono::NaiveDateTime;
 use clap::{Parser, Subcommand};
 
-use crate::auth::{authenticate_user, AuthenticationError};
-use crate::core::CoreError;
-use crate::database::models::{Person, User};
+use diesel::result::Error as DatabaseError;
+use diesel::SqliteConnection;
+
+use crate::auth::permission::Permission;
+use crate::auth::session::read_token_file;
+use crate::auth::{
+    authenticate_api_key, authenticate_schema_admin, authenticate_session, authenticate_user,
+    has_permission, AuthenticationError,
+};
+use crate::config;
+use crate::core::{self, CoreError, SchemaState};
+use crate::database;
+use crate::database::controller::ControllerError;
+use crate::database::models::{MovementKind, Person, User};
+use crate::serializers::{MovementFilter, Sign, SortOrder};
+use crate::utils::date::{parse_since, parse_until};
+use crate::utils::db::join_filters;
+use crate::utils::exchange::ExchangeError;
+use crate::utils::output::{Output, OutputFormat};
+use crate::utils::points::parse_points;
 
 #[derive(Debug)]
 pub enum CliError {
@@ -15,6 +36,130 @@ pub enum CliError {
     Authentication(AuthenticationError),
 }
 
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Core(error) => write!(f, "{}", error),
+            CliError::Authentication(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl StdError for CliError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            CliError::Core(error) => Some(error),
+            CliError::Authentication(error) => Some(error),
+        }
+    }
+}
+
+/// Process exit codes, one per kind of failure. Clap already exits with 2 on usage
+/// errors. Documented in the README.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    Failure = 1,
+    Authentication = 3,
+    NotFound = 4,
+    InsufficientBalance = 5,
+    Validation = 6,
+    Network = 7,
+    Database = 8,
+}
+
+impl CliError {
+    pub fn exit_status(&self) -> ExitStatus {
+        match self {
+            CliError::Core(error) => core_status(error),
+            CliError::Authentication(error) => authentication_status(error),
+        }
+    }
+}
+
+fn core_status(error: &CoreError) -> ExitStatus {
+    match error {
+        CoreError::DatabaseConnection(_) => ExitStatus::Database,
+        CoreError::Database(error) => database_status(error),
+        CoreError::Exchange(error) => exchange_status(error),
+        CoreError::Controller(error) => controller_status(error),
+        CoreError::Io(_) => ExitStatus::Failure,
+        CoreError::Migration(_)
+        | CoreError::SchemaOutdated(_)
+        | CoreError::DatabaseNotInitialized => ExitStatus::Database,
+        CoreError::Import(_)
+        | CoreError::InvalidRows(_)
+        | CoreError::DatabaseAlreadyInitialized
+        | CoreError::PasswordMismatch
+        | CoreError::WeakPassword(_) => ExitStatus::Validation,
+    }
+}
+
+fn authentication_status(error: &AuthenticationError) -> ExitStatus {
+    match error {
+        AuthenticationError::DatabaseConnection(_) => ExitStatus::Database,
+        AuthenticationError::Database(DatabaseError::NotFound) => ExitStatus::Authentication,
+        AuthenticationError::Database(_) => ExitStatus::Database,
+        AuthenticationError::Controller(error) => controller_status(error),
+        AuthenticationError::KeyMaterial(_)
+        | AuthenticationError::SecretKeyMissing
+        | AuthenticationError::Io(_) => ExitStatus::Failure,
+        AuthenticationError::UserNotAuthenticated
+        | AuthenticationError::AccessDenied(_)
+        | AuthenticationError::PasswordChangeRequired
+        | AuthenticationError::UserInactive
+        | AuthenticationError::SessionInvalid
+        | AuthenticationError::TwoFactorRequired
+        | AuthenticationError::TwoFactorInvalid
+        | AuthenticationError::ApiKeyInvalid
+        | AuthenticationError::ApiKeyNotAllowed
+        | AuthenticationError::PointsCapExceeded(_) => ExitStatus::Authentication,
+        AuthenticationError::TwoFactorNotEnrolled => ExitStatus::Validation,
+    }
+}
+
+fn controller_status(error: &ControllerError) -> ExitStatus {
+    match error {
+        ControllerError::Database(error) => database_status(error),
+        ControllerError::InsufficientBalance => ExitStatus::InsufficientBalance,
+        ControllerError::PersonNotFound(_)
+        | ControllerError::RoleNotFound(_)
+        | ControllerError::SessionNotFound(_)
+        | ControllerError::ApiKeyNotFound(_)
+        | ControllerError::ServiceAccountNotFound(_) => ExitStatus::NotFound,
+        ControllerError::Validation(_)
+        | ControllerError::UsernameTaken(_)
+        | ControllerError::UsernameReserved(_)
+        | ControllerError::InvalidAmount
+        | ControllerError::SelfTransfer
+        | ControllerError::PersonInactive(_) => ExitStatus::Validation,
+        ControllerError::TargetOutranks(_, _) | ControllerError::RoleOutranks(_, _) => {
+            ExitStatus::Authentication
+        }
+        ControllerError::RoleFixed(_) => ExitStatus::Validation,
+        ControllerError::PasswordHash(_) => ExitStatus::Failure,
+        ControllerError::Exchange(error) => exchange_status(error),
+    }
+}
+
+fn exchange_status(error: &ExchangeError) -> ExitStatus {
+    match error {
+        ExchangeError::Reqwest(_) | ExchangeError::Serde(_) | ExchangeError::Parse(_) => {
+            ExitStatus::Network
+        }
+        ExchangeError::NotFound(_) => ExitStatus::NotFound,
+        ExchangeError::Toml(_) | ExchangeError::Io(_) | ExchangeError::Config(_) => {
+            ExitStatus::Failure
+        }
+    }
+}
+
+fn database_status(error: &DatabaseError) -> ExitStatus {
+    match error {
+        DatabaseError::NotFound => ExitStatus::NotFound,
+        _ => ExitStatus::Database,
+    }
+}
+
 impl From<CoreError> for CliError {
     fn from(value: CoreError) -> Self {
         Self::Core(value)
@@ -38,12 +183,22 @@ impl From<AuthenticationError> for CliError {
 pub struct Cli {
     #[command(subcommand)]
     command: Commands,
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table, help = "Output format")]
+    format: OutputFormat,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     #[command(about = "Loads the file to the database.", long_about = None)]
-    Load { filepath: String },
+    Load {
+        filepath: String,
+        #[arg(long, help = "Loads the valid rows and reports the invalid ones")]
+        skip_invalid: bool,
+        #[arg(long, help = "Shows what would change without writing anything")]
+        dry_run: bool,
+        #[arg(long, help = "Deactivates the people that are not in the file")]
+        sync: bool,
+    },
     #[command(about = "Shows information about user or dept.", long_about = None)]
     Show {
         #[arg(short, long)]
@@ -53,101 +208,707 @@ enum Commands {
     },
     #[command(about = "Add points to the user or dept.", long_about = None)]
     Add {
-        value: f32,
+        #[arg(value_parser = parse_points)]
+        value: i64,
         #[arg(short, long)]
         dept: Option<String>,
         #[arg(short, long)]
         email: Option<String>,
+        #[arg(short, long)]
+        memo: Option<String>,
     },
     #[command(about = "Remove points to the user or dept.", long_about = None)]
     Remove {
-        value: f32,
+        #[arg(value_parser = parse_points)]
+        value: i64,
         #[arg(short, long)]
         dept: Option<String>,
         #[arg(short, long)]
         email: Option<String>,
+        #[arg(short, long)]
+        memo: Option<String>,
     },
     #[command(about = "Transfer points to a specific user.", long_about = None)]
     Transfer {
-        #[arg(short, long)]
-        value: f32,
+        #[arg(short, long, value_parser = parse_points)]
+        value: i64,
         #[arg(short, long)]
         to: String,
+        #[arg(short, long)]
+        memo: Option<String>,
     },
     #[command(about = "Lists movements.", long_about = None)]
-    Movements,
+    Movements {
+        #[arg(
+            short,
+            long,
+            help = "Combined statement of a department (requires people.read)"
+        )]
+        dept: Option<String>,
+        #[arg(
+            short,
+            long,
+            help = "Statement of another person (requires people.read)"
+        )]
+        email: Option<String>,
+        #[arg(
+            long,
+            value_parser = parse_since,
+            help = "Only movements from this date on (YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS)"
+        )]
+        since: Option<NaiveDateTime>,
+        #[arg(long, value_parser = parse_until, help = "Only movements up to this date, inclusive")]
+        until: Option<NaiveDateTime>,
+        #[arg(long, help = "Only movements made by this user")]
+        actor: Option<String>,
+        #[arg(long, value_enum, help = "Only credits or only debits")]
+        sign: Option<Sign>,
+        #[arg(long, value_parser = MovementKind::from_str, help = "Only movements of this kind")]
+        kind: Option<MovementKind>,
+        #[arg(long, value_enum, default_value_t = SortOrder::Asc)]
+        order: SortOrder,
+        #[arg(long, help = "Shows at most this many movements")]
+        limit: Option<usize>,
+        #[arg(long, default_value_t = 0, help = "Skips this many movements")]
+        offset: usize,
+    },
+    #[command(about = "Changes the password of the current user.", long_about = None)]
+    Passwd,
+    #[command(about = "Opens a session so later commands do not ask for the password.", long_about = None)]
+    Login,
+    #[command(about = "Closes the current session.", long_about = None)]
+    Logout,
+    #[command(about = "Manages login sessions.", long_about = None)]
+    Session {
+        #[command(subcommand)]
+        command: SessionCommands,
+    },
+    #[command(about = "Manages user accounts.", long_about = None)]
+    User {
+        #[command(subcommand)]
+        command: UserCommands,
+    },
+    #[command(about = "Manages service accounts and their API keys.", long_about = None)]
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+    #[command(about = "Database administration.", long_about = None)]
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
 }
 
-impl Authenticated for Commands {
-    fn authenticate(
-        &self,
-        requires_superuser: bool,
-    ) -> Result<(Person, User), AuthenticationError> {
-        let mut username = String::new();
-        let password;
+#[derive(Subcommand)]
+enum DbCommands {
+    #[command(about = "Creates the schema of a new database.", long_about = None)]
+    Init,
+    #[command(about = "Applies the pending migrations.", long_about = None)]
+    Migrate,
+    #[command(about = "Lists the migrations and whether they were applied.", long_about = None)]
+    Status,
+    #[command(about = "Reverts the last applied migrations.", long_about = None)]
+    Rollback {
+        #[arg(long, default_value_t = 1, help = "How many migrations to revert")]
+        steps: usize,
+    },
+    #[command(about = "Checks balances against the movement log.", long_about = None)]
+    Verify {
+        #[arg(long, help = "Rebuilds every balance from the movement log")]
+        repair: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceCommands {
+    #[command(about = "Creates a service account.", long_about = None)]
+    Create {
+        #[arg(short, long)]
+        name: String,
+    },
+    #[command(about = "Issues an API key for a service account and prints it once.", long_about = None)]
+    IssueKey {
+        #[arg(short, long, help = "The service account")]
+        name: String,
+        #[arg(
+            short,
+            long = "scope",
+            required = true,
+            value_parser = Permission::from_str,
+            help = "A permission the key grants, can be repeated"
+        )]
+        scopes: Vec<Permission>,
+        #[arg(long, value_parser = parse_points, help = "Most points the key may move in a single call")]
+        max_points: Option<i64>,
+        #[arg(long, value_parser = parse_until, help = "Last day the key is valid (YYYY-MM-DD)")]
+        expires: NaiveDateTime,
+    },
+    #[command(about = "Lists API keys.", long_about = None)]
+    Keys,
+    #[command(about = "Revokes an API key.", long_about = None)]
+    RevokeKey { id: String },
+}
 
-        match env::var("DUNDIE_USER") {
-            Ok(user) => username = user,
-            Err(_) => {
-                print!("username: ",);
-                let _ = io::stdout().flush();
-                io::stdin().read_line(&mut username).unwrap();
-                username = username.trim().to_string();
+#[derive(Subcommand)]
+enum SessionCommands {
+    #[command(about = "Lists open sessions.", long_about = None)]
+    List {
+        #[arg(long, help = "Also lists revoked and expired sessions")]
+        all: bool,
+    },
+    #[command(about = "Revokes a session.", long_about = None)]
+    Revoke { id: String },
+}
+
+#[derive(Subcommand)]
+enum TwoFactorCommands {
+    #[command(about = "Creates a TOTP secret and prints it as an otpauth:// URI.", long_about = None)]
+    Enroll,
+    #[command(about = "Turns two-factor authentication on and prints recovery codes.", long_about = None)]
+    Activate {
+        #[arg(short, long, help = "A code from the authenticator app")]
+        code: String,
+    },
+    #[command(about = "Turns two-factor authentication off.", long_about = None)]
+    Disable {
+        #[arg(
+            short,
+            long,
+            help = "Turns it off for another user (requires users.manage)"
+        )]
+        email: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum UserCommands {
+    #[command(about = "Issues a temporary password that must be changed on next login.", long_about = None)]
+    ResetPassword {
+        #[arg(short, long)]
+        email: String,
+        #[arg(
+            short,
+            long,
+            help = "Writes the credential to this file instead of printing it"
+        )]
+        output: Option<String>,
+    },
+    #[command(about = "Lifts a lockout caused by failed logins.", long_about = None)]
+    Unlock {
+        #[arg(short, long)]
+        email: String,
+    },
+    #[command(name = "2fa", about = "Manages two-factor authentication.", long_about = None)]
+    TwoFactor {
+        #[command(subcommand)]
+        command: TwoFactorCommands,
+    },
+    #[command(about = "Changes the role of a user.", long_about = None)]
+    SetRole {
+        #[arg(short, long)]
+        email: String,
+        #[arg(short, long)]
+        role: String,
+    },
+}
+
+impl Commands {
+    /// The permission a command needs, checked on authentication before it runs.
+    fn permission(&self) -> Option<Permission> {
+        match self {
+            Commands::Load { .. } => Some(Permission::PeopleWrite),
+            Commands::Show { dept, email } | Commands::Movements { dept, email, .. } => {
+                if dept.is_some() || email.is_some() {
+                    Some(Permission::PeopleRead)
+                } else {
+                    None
+                }
             }
+            Commands::Add { .. } | Commands::Remove { .. } => Some(Permission::PointsGrant),
+            Commands::Transfer { .. } => Some(Permission::PointsTransfer),
+            Commands::Passwd | Commands::Login | Commands::Logout => None,
+            Commands::Session { .. } => Some(Permission::UsersManage),
+            Commands::User { command } => match command {
+                UserCommands::ResetPassword { .. } | UserCommands::Unlock { .. } => {
+                    Some(Permission::UsersManage)
+                }
+                UserCommands::SetRole { .. } => Some(Permission::RolesManage),
+                UserCommands::TwoFactor {
+                    command: TwoFactorCommands::Disable { email: Some(_) },
+                } => Some(Permission::UsersManage),
+                UserCommands::TwoFactor { .. } => None,
+            },
+            Commands::Service { .. } => Some(Permission::ServicesManage),
+            Commands::Db {
+                command: DbCommands::Init,
+            } => None,
+            Commands::Db { .. } => Some(Permission::DatabaseAdmin),
         }
-        match env::var("DUNDIE_PWD") {
-            Ok(pass) => password = pass,
-            Err(_) => {
-                password = rpassword::prompt_password("password: ").unwrap();
+    }
+
+    /// Commands that work on the schema itself. They run without the schema check, since
+    /// the user table may not exist yet or be out of date.
+    fn manages_schema(&self) -> bool {
+        matches!(
+            self,
+            Commands::Db {
+                command: DbCommands::Init
+                    | DbCommands::Migrate
+                    | DbCommands::Status
+                    | DbCommands::Rollback { .. }
+            }
+        )
+    }
+
+    /// Authenticates `db migrate`, `db status` and `db rollback`. `migrate` and `status`
+    /// run without credentials while there is no `user` table to log in with. While
+    /// migrations are pending only the password and the permission can be checked, see
+    /// [`authenticate_schema_admin`].
+    fn authenticate_schema_command(&self) -> Result<(), CliError> {
+        match core::schema_state()? {
+            SchemaState::WithoutUsers => match self {
+                Commands::Db {
+                    command: DbCommands::Rollback { .. },
+                } => Err(AuthenticationError::UserNotAuthenticated.into()),
+                _ => Ok(()),
+            },
+            SchemaState::Outdated => {
+                let (username, password) = read_credentials();
+                let mut connection = database::connection::establish_connection()
+                    .map_err(AuthenticationError::from)?;
+                Ok(authenticate_schema_admin(
+                    &mut connection,
+                    &username,
+                    &password,
+                )?)
+            }
+            SchemaState::Current => {
+                self.authenticate()?;
+                Ok(())
             }
         }
+    }
 
-        authenticate_user(&username, &password, requires_superuser)
+    /// Commands a user runs to set up their own credentials. They are let through while
+    /// the password must still be changed or a required second factor is missing.
+    /// Turning off the second factor of someone else is not one of them.
+    fn credential_setup(&self) -> bool {
+        matches!(
+            self,
+            Commands::Passwd
+                | Commands::User {
+                    command: UserCommands::TwoFactor {
+                        command: TwoFactorCommands::Enroll
+                            | TwoFactorCommands::Activate { .. }
+                            | TwoFactorCommands::Disable { email: None }
+                    }
+                }
+        )
+    }
+
+    /// The points a command moves in total, for the cap of API keys. `add` and `remove`
+    /// move the value once for every person the filters match.
+    fn points(&self, connection: &mut SqliteConnection) -> Result<Option<i64>, ControllerError> {
+        match self {
+            Commands::Add {
+                value, dept, email, ..
+            }
+            | Commands::Remove {
+                value, dept, email, ..
+            } => {
+                let people = database::controller::query_grant_targets(
+                    connection,
+                    &join_filters(dept, email),
+                )?;
+                Ok(Some(value.saturating_mul(people.len() as i64)))
+            }
+            Commands::Transfer { value, .. } => Ok(Some(*value)),
+            _ => Ok(None),
+        }
     }
 }
 
+impl Authenticated for Commands {
+    fn authenticate(&self) -> Result<(Person, User), AuthenticationError> {
+        let permission = self.permission();
+        let credential_setup = self.credential_setup();
+
+        if let Ok(key) = env::var("DUNDIE_API_KEY") {
+            let mut connection = database::connection::establish_connection()?;
+            let points = self.points(&mut connection)?;
+            return authenticate_api_key(&mut connection, &key, permission, points);
+        }
+
+        // A session is only used when no username is given explicitly, and a session
+        // that is no longer valid falls back to asking for the credentials.
+        if env::var("DUNDIE_USER").is_err() && !matches!(self, Commands::Login) {
+            if let Some(token) = read_token_file(&config::session_settings().file) {
+                match authenticate_session(&token, permission, credential_setup) {
+                    Err(AuthenticationError::SessionInvalid)
+                    | Err(AuthenticationError::SecretKeyMissing) => {}
+                    result => return result,
+                }
+            }
+        }
+
+        let (username, password) = read_credentials();
+
+        let second_factor = || match env::var("DUNDIE_OTP") {
+            Ok(code) => code,
+            Err(_) => rpassword::prompt_password("two-factor code: ").unwrap(),
+        };
+
+        authenticate_user(
+            &username,
+            &password,
+            second_factor,
+            permission,
+            credential_setup,
+        )
+    }
+}
+
+/// Reads the username and password from `DUNDIE_USER` and `DUNDIE_PWD`, or asks for them.
+fn read_credentials() -> (String, String) {
+    let mut username = String::new();
+
+    match env::var("DUNDIE_USER") {
+        Ok(user) => username = user,
+        Err(_) => {
+            print!("username: ",);
+            let _ = io::stdout().flush();
+            io::stdin().read_line(&mut username).unwrap();
+            username = username.trim().to_string();
+        }
+    }
+    let password = match env::var("DUNDIE_PWD") {
+        Ok(pass) => pass,
+        Err(_) => rpassword::prompt_password("password: ").unwrap(),
+    };
+
+    (username, password)
+}
+
 pub fn match_command(cli: &Cli) -> Result<(), CliError> {
+    let mut output = Output::new(cli.format);
+    let result = run_command(cli, &mut output);
+    output.finish();
+    result
+}
+
+fn run_command(cli: &Cli, output: &mut Output) -> Result<(), CliError> {
+    if !cli.command.manages_schema() {
+        core::check_schema()?;
+    }
+
     match &cli.command {
-        Commands::Load { filepath } => {
-            let _ = &cli.command.authenticate(true)?;
-            commands::load::run(filepath)?;
-            return Ok(());
+        Commands::Load {
+            filepath,
+            skip_invalid,
+            dry_run,
+            sync,
+        } => {
+            let _ = &cli.command.authenticate()?;
+            commands::load::run(output, filepath, *skip_invalid, *dry_run, *sync)?;
+            Ok(())
         }
         Commands::Show { dept, email } => {
-            let (person, user) = &cli.command.authenticate(false)?;
+            let (person, user) = &cli.command.authenticate()?;
 
-            if user.superuser {
-                commands::show::run(dept, email)?;
+            // Filters were already checked against people.read on authentication.
+            if dept.is_some() || email.is_some() || has_permission(user, Permission::PeopleRead)? {
+                commands::show::run(output, dept, email)?;
             } else {
-                commands::show::run(&None, &Some(person.email.clone()))?;
+                commands::show::run(output, &None, &Some(person.email.clone()))?;
             }
 
-            return Ok(());
+            Ok(())
         }
-        Commands::Add { value, dept, email } => {
-            let (_, user) = &cli.command.authenticate(true)?;
-            commands::add::run(user, *value, dept, email)?;
-            return Ok(());
+        Commands::Add {
+            value,
+            dept,
+            email,
+            memo,
+        } => {
+            let (_, user) = &cli.command.authenticate()?;
+            commands::add::run(output, user, *value, dept, email, memo)?;
+            Ok(())
         }
-        Commands::Remove { value, dept, email } => {
-            let (_, user) = &cli.command.authenticate(true)?;
-            commands::add::run(user, -(*value), dept, email)?;
-            return Ok(());
+        Commands::Remove {
+            value,
+            dept,
+            email,
+            memo,
+        } => {
+            let (_, user) = &cli.command.authenticate()?;
+            commands::add::run(output, user, -(*value), dept, email, memo)?;
+            Ok(())
         }
-        Commands::Transfer { value, to } => {
-            let (sender, user) = &cli.command.authenticate(false)?;
-            commands::transfer::run(sender, user, *value, to)?;
-            return Ok(());
+        Commands::Transfer { value, to, memo } => {
+            let (sender, user) = &cli.command.authenticate()?;
+            commands::transfer::run(output, sender, user, *value, to, memo)?;
+            Ok(())
         }
-        Commands::Movements => {
-            let (_, user) = &cli.command.authenticate(false)?;
-            commands::movements::run(user)?;
-            return Ok(());
+        Commands::Movements {
+            dept,
+            email,
+            since,
+            until,
+            actor,
+            sign,
+            kind,
+            order,
+            limit,
+            offset,
+        } => {
+            let (_, user) = &cli.command.authenticate()?;
+            let filter = MovementFilter {
+                since: *since,
+                until: *until,
+                actor: actor.clone(),
+                sign: *sign,
+                kind: *kind,
+                order: *order,
+                limit: *limit,
+                offset: *offset,
+            };
+
+            if dept.is_some() || email.is_some() {
+                commands::movements::run_combined(output, dept, email, &filter)?;
+            } else {
+                commands::movements::run(output, user, &filter)?;
+            }
+
+            Ok(())
+        }
+        Commands::Passwd => {
+            let (_, user) = &cli.command.authenticate()?;
+            commands::passwd::run(user)?;
+            Ok(())
+        }
+        Commands::Login => {
+            let (_, user) = &cli.command.authenticate()?;
+            commands::session::login(user)?;
+            Ok(())
+        }
+        Commands::Logout => {
+            commands::session::logout()?;
+            Ok(())
+        }
+        Commands::Session { command } => {
+            let _ = &cli.command.authenticate()?;
+            match command {
+                SessionCommands::List { all } => commands::session::list(output, *all)?,
+                SessionCommands::Revoke { id } => commands::session::revoke(id)?,
+            }
+            Ok(())
         }
+        Commands::User { command } => match command {
+            UserCommands::ResetPassword { email, output } => {
+                let (_, user) = &cli.command.authenticate()?;
+                commands::user::reset_password(user, email, output)?;
+                Ok(())
+            }
+            UserCommands::Unlock { email } => {
+                let (_, user) = &cli.command.authenticate()?;
+                commands::user::unlock(user, email)?;
+                Ok(())
+            }
+            UserCommands::TwoFactor { command } => {
+                let (_, user) = &cli.command.authenticate()?;
+                match command {
+                    TwoFactorCommands::Enroll => commands::two_factor::enroll(user)?,
+                    TwoFactorCommands::Activate { code } => {
+                        commands::two_factor::activate(user, code)?
+                    }
+                    TwoFactorCommands::Disable { email: None } => {
+                        commands::two_factor::disable(user)?
+                    }
+                    TwoFactorCommands::Disable { email: Some(email) } => {
+                        commands::two_factor::disable_for(user, email)?
+                    }
+                }
+                Ok(())
+            }
+            UserCommands::SetRole { email, role } => {
+                let (_, user) = &cli.command.authenticate()?;
+                commands::user::set_role(user, email, role)?;
+                Ok(())
+            }
+        },
+        Commands::Service { command } => {
+            let _ = &cli.command.authenticate()?;
+            match command {
+                ServiceCommands::Create { name } => commands::service::create(name)?,
+                ServiceCommands::IssueKey {
+                    name,
+                    scopes,
+                    max_points,
+                    expires,
+                } => commands::service::issue_key(name, scopes, *max_points, *expires)?,
+                ServiceCommands::Keys => commands::service::keys(output)?,
+                ServiceCommands::RevokeKey { id } => commands::service::revoke_key(id)?,
+            }
+            Ok(())
+        }
+        Commands::Db { command } => match command {
+            DbCommands::Verify { repair } => {
+                let _ = &cli.command.authenticate()?;
+                commands::db::verify(*repair)?;
+                Ok(())
+            }
+            DbCommands::Init => {
+                commands::db::init()?;
+                Ok(())
+            }
+            DbCommands::Migrate => {
+                cli.command.authenticate_schema_command()?;
+                commands::db::migrate()?;
+                Ok(())
+            }
+            DbCommands::Status => {
+                cli.command.authenticate_schema_command()?;
+                commands::db::status(output)?;
+                Ok(())
+            }
+            DbCommands::Rollback { steps } => {
+                cli.command.authenticate_schema_command()?;
+                commands::db::rollback(*steps)?;
+                Ok(())
+            }
+        },
     }
 }
 
 pub trait Authenticated {
-    fn authenticate(&self, requires_superuser: bool)
-        -> Result<(Person, User), AuthenticationError>;
+    fn authenticate(&self) -> Result<(Person, User), AuthenticationError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::result::Error as DatabaseError;
+    use diesel::SqliteConnection;
+    use rstest::rstest;
+
+    use clap::Parser;
+
+    use crate::auth::permission::Permission;
+    use crate::auth::AuthenticationError;
+    use crate::cli::{Cli, CliError, ExitStatus};
+    use crate::core::CoreError;
+    use crate::database::controller::test::{new_person, test_db_connection};
+    use crate::database::controller::{add_person, ControllerError};
+    use crate::database::models::NewPerson;
+    use crate::utils::exchange::ExchangeError;
+
+    #[rstest]
+    #[case(
+        CliError::Authentication(AuthenticationError::UserNotAuthenticated),
+        ExitStatus::Authentication
+    )]
+    #[case(
+        CliError::Authentication(AuthenticationError::Database(DatabaseError::NotFound)),
+        ExitStatus::Authentication
+    )]
+    #[case(
+        CliError::Core(CoreError::Controller(ControllerError::PersonNotFound("x".to_string()))),
+        ExitStatus::NotFound
+    )]
+    #[case(
+        CliError::Core(CoreError::Controller(ControllerError::InsufficientBalance)),
+        ExitStatus::InsufficientBalance
+    )]
+    #[case(CliError::Core(CoreError::InvalidRows(vec![])), ExitStatus::Validation)]
+    #[case(
+        CliError::Core(CoreError::Exchange(ExchangeError::NotFound("EUR".to_string()))),
+        ExitStatus::NotFound
+    )]
+    #[case(
+        CliError::Core(CoreError::Database(DatabaseError::RollbackTransaction)),
+        ExitStatus::Database
+    )]
+    fn error_exit_status(#[case] error: CliError, #[case] expected: ExitStatus) {
+        assert_eq!(error.exit_status(), expected);
+    }
+
+    #[rstest]
+    fn error_messages() {
+        let error = CliError::Core(CoreError::Controller(ControllerError::InsufficientBalance));
+        assert_eq!(error.to_string(), "insufficient balance");
+
+        let error =
+            CliError::Authentication(AuthenticationError::Database(DatabaseError::NotFound));
+        assert_eq!(error.to_string(), "invalid username or password");
+    }
+
+    #[rstest]
+    #[case(&["load", "people.csv"], Some(Permission::PeopleWrite))]
+    #[case(&["show"], None)]
+    #[case(&["show", "--dept", "Sales"], Some(Permission::PeopleRead))]
+    #[case(&["movements", "--email", "jim@dm.com"], Some(Permission::PeopleRead))]
+    #[case(&["add", "10", "--dept", "Sales"], Some(Permission::PointsGrant))]
+    #[case(&["transfer", "--value", "10", "--to", "jim@dm.com"], Some(Permission::PointsTransfer))]
+    #[case(&["user", "set-role", "-e", "jim@dm.com", "-r", "finance"], Some(Permission::RolesManage))]
+    #[case(&["db", "verify"], Some(Permission::DatabaseAdmin))]
+    #[case(&["db", "init"], None)]
+    #[case(&["db", "migrate"], Some(Permission::DatabaseAdmin))]
+    #[case(&["db", "status"], Some(Permission::DatabaseAdmin))]
+    #[case(&["db", "rollback"], Some(Permission::DatabaseAdmin))]
+    #[case(&["service", "keys"], Some(Permission::ServicesManage))]
+    #[case(
+        &["service", "issue-key", "-n", "payroll", "-s", "points.grant", "--expires", "2027-01-31"],
+        Some(Permission::ServicesManage)
+    )]
+    #[case(&["user", "2fa", "enroll"], None)]
+    #[case(&["user", "2fa", "disable", "--email", "jim@dm.com"], Some(Permission::UsersManage))]
+    fn command_permission(#[case] args: &[&str], #[case] expected: Option<Permission>) {
+        let cli =
+            Cli::try_parse_from(std::iter::once("dundie").chain(args.iter().copied())).unwrap();
+        assert_eq!(cli.command.permission(), expected);
+    }
+
+    #[rstest]
+    #[case(&["passwd"], true)]
+    #[case(&["user", "2fa", "enroll"], true)]
+    #[case(&["user", "2fa", "activate", "--code", "123456"], true)]
+    #[case(&["user", "2fa", "disable"], true)]
+    #[case(&["user", "2fa", "disable", "--email", "jim@dm.com"], false)]
+    #[case(&["user", "reset-password", "--email", "jim@dm.com"], false)]
+    fn command_credential_setup(#[case] args: &[&str], #[case] expected: bool) {
+        let cli =
+            Cli::try_parse_from(std::iter::once("dundie").chain(args.iter().copied())).unwrap();
+        assert_eq!(cli.command.credential_setup(), expected);
+    }
+
+    #[rstest]
+    #[case(&["add", "12.5", "--dept", "Sales"], Some(2500))]
+    #[case(&["remove", "3", "--email", "john-doe@dm.com"], Some(300))]
+    #[case(&["add", "5", "--dept", "Nobody"], Some(0))]
+    #[case(&["add", "1"], Some(200))]
+    #[case(&["transfer", "--value", "7", "--to", "john-doe@dm.com"], Some(700))]
+    #[case(&["show"], None)]
+    fn command_points(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+        #[case] args: &[&str],
+        #[case] expected: Option<i64>,
+    ) {
+        add_person(&mut test_db_connection, &new_person).unwrap();
+        add_person(
+            &mut test_db_connection,
+            &NewPerson {
+                email: "jim@dm.com".to_string(),
+                ..new_person
+            },
+        )
+        .unwrap();
+
+        let cli =
+            Cli::try_parse_from(std::iter::once("dundie").chain(args.iter().copied())).unwrap();
+        assert_eq!(
+            cli.command.points(&mut test_db_connection).unwrap(),
+            expected
+        );
+    }
 }
@@ -1,5 +1,11 @@
 pub mod add;
+pub mod db;
 pub mod load;
 pub mod movements;
+pub mod passwd;
+pub mod service;
+pub mod session;
 pub mod show;
 pub mod transfer;
+pub mod two_factor;
+pub mod user;
@@ -1,19 +1,21 @@
 use crate::core::{move_points, search, CoreError};
 use crate::database::models::User;
-use crate::utils::cli::print_person;
 use crate::utils::db::join_filters;
+use crate::utils::output::Output;
 
 pub fn run(
+    output: &mut Output,
     user: &User,
-    value: f32,
+    value: i64,
     dept: &Option<String>,
     email: &Option<String>,
+    memo: &Option<String>,
 ) -> Result<(), CoreError> {
     let query = join_filters(dept, email);
-    move_points(value, &user.username, &query)?;
+    move_points(value, &user.username, &query, memo)?;
 
     let people = search(&query)?;
-    print_person(people, vec!["created"]);
+    output.records("people", &people, &["created"]);
 
     Ok(())
 }
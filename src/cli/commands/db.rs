@@ -0,0 +1,74 @@
+use crate::core::{
+    database_status, init_database, migrate_database, rollback_database, verify_ledger, CoreError,
+};
+use crate::utils::cli::print_ledger_issues;
+use crate::utils::output::Output;
+
+pub fn init() -> Result<(), CoreError> {
+    let applied = init_database()?;
+    println!(
+        "Success.. database initialized with {} migration(s).",
+        applied.len()
+    );
+    println!("Log in as admin and change its password with `passwd`.");
+    Ok(())
+}
+
+pub fn migrate() -> Result<(), CoreError> {
+    let applied = migrate_database()?;
+
+    if applied.is_empty() {
+        println!("Success.. the database is up to date.");
+        return Ok(());
+    }
+
+    for name in &applied {
+        println!("Applied {}", name);
+    }
+    println!("Success.. {} migration(s) applied.", applied.len());
+    Ok(())
+}
+
+pub fn status(output: &mut Output) -> Result<(), CoreError> {
+    let migrations = database_status()?;
+    output.records("migrations", &migrations, &[]);
+    Ok(())
+}
+
+pub fn rollback(steps: usize) -> Result<(), CoreError> {
+    let reverted = rollback_database(steps)?;
+
+    for name in &reverted {
+        println!("Reverted {}", name);
+    }
+    println!("Success.. {} migration(s) reverted.", reverted.len());
+    Ok(())
+}
+
+pub fn verify(repair: bool) -> Result<(), CoreError> {
+    let (issues, rebuilt, remaining) = verify_ledger(repair)?;
+
+    if issues.is_empty() {
+        println!("Success.. no ledger issues found.");
+        return Ok(());
+    }
+
+    println!("{} ledger issue(s) found:", issues.len());
+    print_ledger_issues(&issues);
+
+    if repair {
+        println!("Rebuilt {} balance(s) from the movement log.", rebuilt);
+
+        if remaining.is_empty() {
+            println!("Success.. no ledger issues left.");
+        } else {
+            println!(
+                "{} issue(s) cannot be repaired automatically:",
+                remaining.len()
+            );
+            print_ledger_issues(&remaining);
+        }
+    }
+
+    Ok(())
+}
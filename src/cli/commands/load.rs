@@ -1,9 +1,55 @@
 use crate::core::{load, CoreError};
-use crate::utils::cli::print_person;
+use crate::utils::output::Output;
 
-pub fn run(filepath: &String) -> Result<(), CoreError> {
-    let people = load(filepath.to_string())?;
-    print_person(people, vec!["balance", "value"]);
+pub fn run(
+    output: &mut Output,
+    filepath: &String,
+    skip_invalid: bool,
+    dry_run: bool,
+    sync: bool,
+) -> Result<(), CoreError> {
+    let report = match load(filepath.to_string(), skip_invalid, dry_run, sync) {
+        Ok(report) => report,
+        Err(CoreError::InvalidRows(row_errors)) => {
+            output.message(&format!(
+                "Nothing loaded, {} problem(s) found in the file (use --skip-invalid to load the valid rows):",
+                row_errors.len()
+            ));
+            output.records("row_errors", &row_errors, &[]);
+            return Err(CoreError::InvalidRows(row_errors));
+        }
+        Err(error) => return Err(error),
+    };
+
+    if dry_run {
+        output.message("Dry run, nothing was written to the database.");
+    }
+
+    if !report.row_errors.is_empty() {
+        output.message(&format!(
+            "Skipped the invalid rows, {} problem(s) found:",
+            report.row_errors.len()
+        ));
+        output.records("row_errors", &report.row_errors, &[]);
+    }
+
+    output.records("people", &report.people, &["balance", "value"]);
+
+    if !report.changes.is_empty() {
+        output.message("Changed fields on existing people:");
+        output.records("changes", &report.changes, &[]);
+    }
+
+    if !report.missing.is_empty() {
+        if sync {
+            output.message("People missing from the file, deactivated:");
+        } else {
+            output.message(
+                "People in the database but missing from the file (use --sync to deactivate):",
+            );
+        }
+        output.records("missing", &report.missing, &["created", "balance", "value"]);
+    }
 
     Ok(())
 }
@@ -1,9 +1,24 @@
-use crate::core::{get_statement, CoreError};
+use crate::core::{get_combined_statement, get_statement, CoreError};
 use crate::database::models::User;
-use crate::utils::cli::print_statement;
+use crate::serializers::MovementFilter;
+use crate::utils::db::join_filters;
+use crate::utils::output::Output;
 
-pub fn run(user: &User) -> Result<(), CoreError> {
-    let (balance, movements) = get_statement(user.person_id)?;
-    print_statement(balance, movements);
+pub fn run(output: &mut Output, user: &User, filter: &MovementFilter) -> Result<(), CoreError> {
+    let (summary, movements) = get_statement(user.person_id, filter)?;
+    output.records("movements", &movements, &["person"]);
+    output.record("summary", &summary, &[]);
+    Ok(())
+}
+
+pub fn run_combined(
+    output: &mut Output,
+    dept: &Option<String>,
+    email: &Option<String>,
+    filter: &MovementFilter,
+) -> Result<(), CoreError> {
+    let (summary, movements) = get_combined_statement(&join_filters(dept, email), filter)?;
+    output.records("movements", &movements, &[]);
+    output.record("summary", &summary, &[]);
     Ok(())
 }
@@ -0,0 +1,12 @@
+use crate::core::{change_password, CoreError};
+use crate::database::models::User;
+
+pub fn run(user: &User) -> Result<(), CoreError> {
+    let new_password = rpassword::prompt_password("new password: ")?;
+    let confirmation = rpassword::prompt_password("confirm new password: ")?;
+
+    change_password(user, &new_password, &confirmation)?;
+    println!("Success.. password changed for user {}.", user.username);
+
+    Ok(())
+}
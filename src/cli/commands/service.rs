@@ -0,0 +1,42 @@
+use chrono::NaiveDateTime;
+
+use crate::auth::permission::Permission;
+use crate::core::{self, CoreError};
+use crate::utils::output::Output;
+
+pub fn create(name: &str) -> Result<(), CoreError> {
+    let service_account = core::create_service_account(name)?;
+    println!(
+        "Success.. service account {} created, issue it a key with `service issue-key`.",
+        service_account.username
+    );
+    Ok(())
+}
+
+pub fn issue_key(
+    name: &str,
+    scopes: &[Permission],
+    max_points: Option<i64>,
+    expires_at: NaiveDateTime,
+) -> Result<(), CoreError> {
+    let (api_key, key) = core::issue_api_key(name, scopes, max_points, expires_at)?;
+    println!(
+        "Success.. key {} issued to {} until {}:",
+        api_key.id, name, api_key.expires_at
+    );
+    println!("{}", key);
+    println!("Store it now, it cannot be shown again.");
+    Ok(())
+}
+
+pub fn keys(output: &mut Output) -> Result<(), CoreError> {
+    let api_keys = core::list_api_keys()?;
+    output.records("api_keys", &api_keys, &[]);
+    Ok(())
+}
+
+pub fn revoke_key(id: &str) -> Result<(), CoreError> {
+    let api_key = core::revoke_api_key(id)?;
+    println!("Success.. key {} revoked.", api_key.id);
+    Ok(())
+}
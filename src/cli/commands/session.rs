@@ -0,0 +1,33 @@
+use crate::auth::{self, AuthenticationError};
+use crate::core::{self, CoreError};
+use crate::database::models::User;
+use crate::utils::output::Output;
+
+pub fn login(user: &User) -> Result<(), AuthenticationError> {
+    let session = auth::login(user)?;
+    println!(
+        "Success.. logged in as {} until {}.",
+        user.username, session.expires_at
+    );
+    Ok(())
+}
+
+pub fn logout() -> Result<(), AuthenticationError> {
+    match auth::logout()? {
+        Some(session) => println!("Success.. session {} closed.", session.id),
+        None => println!("No active session."),
+    }
+    Ok(())
+}
+
+pub fn list(output: &mut Output, all: bool) -> Result<(), CoreError> {
+    let sessions = core::list_sessions(all)?;
+    output.records("sessions", &sessions, &[]);
+    Ok(())
+}
+
+pub fn revoke(id: &str) -> Result<(), CoreError> {
+    let session = core::revoke_session(id)?;
+    println!("Success.. session {} revoked.", session.id);
+    Ok(())
+}
@@ -1,9 +1,13 @@
 use crate::core::{search, CoreError};
-use crate::utils::cli::print_person;
 use crate::utils::db::join_filters;
+use crate::utils::output::Output;
 
-pub fn run(dept: &Option<String>, email: &Option<String>) -> Result<(), CoreError> {
+pub fn run(
+    output: &mut Output,
+    dept: &Option<String>,
+    email: &Option<String>,
+) -> Result<(), CoreError> {
     let people = search(&join_filters(dept, email))?;
-    print_person(people, vec!["created"]);
+    output.records("people", &people, &["created"]);
     Ok(())
 }
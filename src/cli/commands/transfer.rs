@@ -1,19 +1,26 @@
-use crate::core::{move_points, CoreError};
+use crate::core::{search, transfer_points, CoreError};
 use crate::database::models::{Person, User};
-use crate::utils::cli::print_person;
 use crate::utils::db::join_filters;
+use crate::utils::output::Output;
+use crate::utils::points::format_points;
 
-pub fn run(person: &Person, user: &User, value: f32, to: &String) -> Result<(), CoreError> {
-    let mut query = join_filters(&None, &Some(person.email.clone()));
-    let sender = &move_points(-value, &user.username, &query)?[0];
-
-    query = join_filters(&None, &Some(to.to_string()));
-    let receiver = &move_points(value, &user.username, &query)?[0];
-    println!(
+pub fn run(
+    output: &mut Output,
+    person: &Person,
+    user: &User,
+    value: i64,
+    to: &String,
+    memo: &Option<String>,
+) -> Result<(), CoreError> {
+    let receiver = transfer_points(person, &user.username, value, to, memo)?;
+    output.message(&format!(
         "Success.. {} points transferred from your account to account of {}.",
-        value, receiver.name
-    );
-    print_person(vec![sender.clone()], vec!["created"]);
+        format_points(value),
+        receiver.name
+    ));
+
+    let sender = search(&join_filters(&None, &Some(person.email.clone())))?;
+    output.records("people", &sender, &["created"]);
 
     Ok(())
 }
@@ -0,0 +1,42 @@
+use crate::auth::{self, AuthenticationError};
+use crate::core::{self, CoreError};
+use crate::database::models::User;
+
+pub fn enroll(user: &User) -> Result<(), AuthenticationError> {
+    let uri = auth::enroll_two_factor(user)?;
+    println!("Add this account to your authenticator app:");
+    println!("{}", uri);
+    println!("Then turn two-factor authentication on with `user 2fa activate --code <code>`.");
+    Ok(())
+}
+
+pub fn activate(user: &User, code: &str) -> Result<(), AuthenticationError> {
+    let recovery_codes = auth::activate_two_factor(user, code)?;
+    println!(
+        "Success.. two-factor authentication is on for {}.",
+        user.username
+    );
+    println!("Keep these recovery codes somewhere safe, each one works only once:");
+    for recovery_code in recovery_codes {
+        println!("  {}", recovery_code);
+    }
+    Ok(())
+}
+
+pub fn disable(user: &User) -> Result<(), AuthenticationError> {
+    auth::disable_two_factor(user)?;
+    println!(
+        "Success.. two-factor authentication is off for {}.",
+        user.username
+    );
+    Ok(())
+}
+
+pub fn disable_for(manager: &User, email: &String) -> Result<(), CoreError> {
+    let (person, user) = core::disable_two_factor(manager, email)?;
+    println!(
+        "Success.. two-factor authentication is off for {} ({}).",
+        person.email, user.username
+    );
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+use crate::core::{self, CoreError};
+use crate::database::models::User;
+
+pub fn reset_password(
+    manager: &User,
+    email: &String,
+    output: &Option<String>,
+) -> Result<(), CoreError> {
+    let (person, user, temporary_password) = core::reset_password(manager, email)?;
+    let credential = format!(
+        "username: {}\npassword: {}\n",
+        user.username, temporary_password
+    );
+
+    match output {
+        Some(filepath) => {
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+
+            let mut file = options.open(filepath)?;
+            file.write_all(credential.as_bytes())?;
+            println!(
+                "Success.. temporary credential for {} written to {}.",
+                person.email, filepath
+            );
+        }
+        None => {
+            println!("Success.. temporary credential for {}:", person.email);
+            print!("{}", credential);
+        }
+    }
+    println!("The password must be changed with `passwd` on the next login.");
+
+    Ok(())
+}
+
+pub fn set_role(manager: &User, email: &String, role: &str) -> Result<(), CoreError> {
+    let (person, user) = core::set_role(manager, email, role)?;
+    println!(
+        "Success.. {} ({}) now has the {} role.",
+        person.email, user.username, user.role
+    );
+    Ok(())
+}
+
+pub fn unlock(manager: &User, email: &String) -> Result<(), CoreError> {
+    let (person, user) = core::unlock_user(manager, email)?;
+    println!(
+        "Success.. {} ({}) can log in again.",
+        person.email, user.username
+    );
+    Ok(())
+}
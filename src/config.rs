@@ -0,0 +1,111 @@
+use std::env;
+use std::str::FromStr;
+
+use dotenvy::dotenv;
+
+fn get_or<T: FromStr>(key: &str, default: T) -> T {
+    dotenv().ok();
+
+    match env::var(key) {
+        Ok(value) => value.trim().parse::<T>().unwrap_or(default),
+        Err(_) => default,
+    }
+}
+
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_numbers: bool,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_symbols: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            require_numbers: true,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_symbols: false,
+        }
+    }
+}
+
+pub fn password_policy() -> PasswordPolicy {
+    let default = PasswordPolicy::default();
+
+    PasswordPolicy {
+        min_length: get_or("DUNDIE_PWD_MIN_LENGTH", default.min_length),
+        require_numbers: get_or("DUNDIE_PWD_REQUIRE_NUMBERS", default.require_numbers),
+        require_lowercase: get_or("DUNDIE_PWD_REQUIRE_LOWERCASE", default.require_lowercase),
+        require_uppercase: get_or("DUNDIE_PWD_REQUIRE_UPPERCASE", default.require_uppercase),
+        require_symbols: get_or("DUNDIE_PWD_REQUIRE_SYMBOLS", default.require_symbols),
+    }
+}
+
+/// Locks a user for `minutes` after `max_failures` failed logins in a row. A
+/// `max_failures` of zero turns the lockout off.
+pub struct LockoutPolicy {
+    pub max_failures: i32,
+    pub minutes: i64,
+}
+
+pub fn lockout_policy() -> LockoutPolicy {
+    LockoutPolicy {
+        max_failures: get_or("DUNDIE_LOCKOUT_MAX_FAILURES", 5),
+        minutes: get_or("DUNDIE_LOCKOUT_MINUTES", 15),
+    }
+}
+
+pub struct ExchangeSettings {
+    pub base_currency: String,
+    pub quote: String,
+    pub provider: String,
+    pub url: String,
+    pub file: String,
+    pub rates: String,
+    pub ttl: i64,
+}
+
+pub fn exchange_settings() -> ExchangeSettings {
+    ExchangeSettings {
+        base_currency: get_or("DUNDIE_BASE_CURRENCY", "USD".to_string()).to_uppercase(),
+        quote: get_or("DUNDIE_EXCHANGE_QUOTE", "high".to_string()),
+        provider: get_or("DUNDIE_EXCHANGE_PROVIDER", "http".to_string()),
+        url: get_or(
+            "DUNDIE_EXCHANGE_URL",
+            "https://economia.awesomeapi.com.br/json/last".to_string(),
+        ),
+        file: get_or("DUNDIE_EXCHANGE_FILE", "rates.toml".to_string()),
+        rates: get_or("DUNDIE_EXCHANGE_RATES", String::new()),
+        ttl: get_or("DUNDIE_EXCHANGE_TTL", 3600),
+    }
+}
+
+pub struct SessionSettings {
+    pub secret_key: String,
+    pub ttl: i64,
+    pub file: String,
+}
+
+/// The session file defaults to `~/.dundie-session`, so each OS user has its own.
+pub fn session_settings() -> SessionSettings {
+    let home = env::var("HOME").unwrap_or_else(|_| ".".to_string());
+
+    SessionSettings {
+        secret_key: get_or("DUNDIE_SECRET_KEY", String::new()),
+        ttl: get_or("DUNDIE_SESSION_TTL", 8 * 3600),
+        file: get_or("DUNDIE_SESSION_FILE", format!("{}/.dundie-session", home)),
+    }
+}
+
+/// Roles whose users must enroll in two-factor authentication, from the comma separated
+/// `DUNDIE_2FA_REQUIRED_ROLES`.
+pub fn two_factor_required_roles() -> Vec<String> {
+    get_or("DUNDIE_2FA_REQUIRED_ROLES", String::new())
+        .split(',')
+        .map(|role| role.trim().to_string())
+        .filter(|role| !role.is_empty())
+        .collect()
+}
@@ -1,14 +1,34 @@
 use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
 use std::fs::File;
+use std::io::Error as IoError;
 
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::connection::{AnsiTransactionManager, TransactionManager};
+use diesel::prelude::*;
 use diesel::result::ConnectionError;
 use diesel::result::Error;
+use rust_decimal::Decimal;
 
+use crate::auth::api_key::{generate_api_key, hash_api_key};
+use crate::auth::permission::Permission;
+use crate::config;
 use crate::database;
-use crate::database::controller::ControllerError;
-use crate::database::models::{Balance, Movement};
-use crate::serializers::{PersonIn, PersonOut};
-use crate::utils::exchange::{get_rates, ExchangeError};
+use crate::database::controller::{ControllerError, LedgerIssue};
+use crate::database::migrations::MigrationError;
+use crate::database::models::{
+    ApiKey, MovementKind, NewApiKey, NewMovement, NewPerson, Person, Session, User,
+};
+use crate::serializers::{
+    ApiKeyOut, LoadReport, MigrationOut, MovementFilter, MovementOut, PersonChange, PersonIn,
+    PersonOut, RowError, SessionOut, SortOrder, StatementSummary,
+};
+use crate::utils::currency::round_to_minor_units;
+use crate::utils::exchange::{configured_provider, ExchangeError};
+use crate::utils::import::{parse_people, ImportError};
+use crate::utils::points::points_to_decimal;
+use crate::utils::user::check_password_strength;
 
 #[derive(Debug)]
 pub enum CoreError {
@@ -16,6 +36,65 @@ pub enum CoreError {
     Database(Error),
     Exchange(ExchangeError),
     Controller(ControllerError),
+    Io(IoError),
+    Import(ImportError),
+    Migration(MigrationError),
+    SchemaOutdated(Vec<String>),
+    DatabaseNotInitialized,
+    DatabaseAlreadyInitialized,
+    InvalidRows(Vec<RowError>),
+    PasswordMismatch,
+    WeakPassword(Vec<String>),
+}
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoreError::DatabaseConnection(error) => {
+                write!(f, "could not connect to the database: {}", error)
+            }
+            CoreError::Database(Error::NotFound) => write!(f, "not found"),
+            CoreError::Database(error) => write!(f, "database error: {}", error),
+            CoreError::Exchange(error) => write!(f, "{}", error),
+            CoreError::Controller(error) => write!(f, "{}", error),
+            CoreError::Io(error) => write!(f, "{}", error),
+            CoreError::Import(error) => write!(f, "{}", error),
+            CoreError::Migration(error) => write!(f, "migration failed: {}", error),
+            CoreError::SchemaOutdated(pending) => write!(
+                f,
+                "the database schema is older than this binary ({} pending migration(s)), run `db migrate`",
+                pending.len()
+            ),
+            CoreError::DatabaseNotInitialized => {
+                write!(f, "the database has no schema yet, run `db init`")
+            }
+            CoreError::DatabaseAlreadyInitialized => {
+                write!(f, "the database is already initialized, run `db migrate` to upgrade it")
+            }
+            CoreError::InvalidRows(row_errors) => {
+                write!(f, "{} problem(s) found in the file", row_errors.len())
+            }
+            CoreError::PasswordMismatch => write!(f, "the passwords do not match"),
+            CoreError::WeakPassword(violations) => {
+                write!(f, "the password {}", violations.join(", "))
+            }
+        }
+    }
+}
+
+impl StdError for CoreError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            CoreError::DatabaseConnection(error) => Some(error),
+            CoreError::Database(error) => Some(error),
+            CoreError::Exchange(error) => Some(error),
+            CoreError::Controller(error) => Some(error),
+            CoreError::Io(error) => Some(error),
+            CoreError::Import(error) => Some(error),
+            CoreError::Migration(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
 }
 
 impl From<ExchangeError> for CoreError {
@@ -42,60 +121,210 @@ impl From<ControllerError> for CoreError {
     }
 }
 
-pub fn load(filepath: String) -> Result<Vec<PersonOut>, CoreError> {
-    let input = File::open(filepath).expect("Error reading the file");
-    let mut result: Vec<PersonOut> = Vec::new();
+impl From<MigrationError> for CoreError {
+    fn from(value: MigrationError) -> Self {
+        Self::Migration(value)
+    }
+}
 
-    let mut rdr = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .from_reader(input);
+impl From<ImportError> for CoreError {
+    fn from(value: ImportError) -> Self {
+        Self::Import(value)
+    }
+}
+
+impl From<IoError> for CoreError {
+    fn from(value: IoError) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Loads the people file into the database.
+///
+/// Every row is validated before anything is written. When some rows are invalid the
+/// whole load is refused with `CoreError::InvalidRows`, unless `skip_invalid` is set,
+/// in which case the valid rows are loaded and the invalid ones are reported.
+///
+/// Active people that are not in the file are reported as missing, and with `sync`
+/// they are also deactivated.
+///
+/// The load runs in a single transaction, so it is all-or-nothing. With `dry_run` the
+/// transaction is always rolled back and the report only shows what would change.
+pub fn load(
+    filepath: String,
+    skip_invalid: bool,
+    dry_run: bool,
+    sync: bool,
+) -> Result<LoadReport, CoreError> {
+    let input = File::open(filepath)?;
+    let (records, row_errors) = parse_people(input)?;
+
+    if !row_errors.is_empty() && !skip_invalid {
+        return Err(CoreError::InvalidRows(row_errors));
+    }
 
     let mut connection = database::connection::establish_connection()?;
+    let mut report = load_records(&mut connection, records, dry_run, sync)?;
+    report.row_errors = row_errors;
+
+    Ok(report)
+}
+
+fn load_records(
+    connection: &mut SqliteConnection,
+    records: Vec<PersonIn>,
+    dry_run: bool,
+    sync: bool,
+) -> Result<LoadReport, CoreError> {
+    if dry_run {
+        AnsiTransactionManager::begin_transaction(connection)?;
+        let result = load_people(connection, records, sync);
+        AnsiTransactionManager::rollback_transaction(connection)?;
+        result
+    } else {
+        connection.transaction(|connection| load_people(connection, records, sync))
+    }
+}
+
+fn load_people(
+    connection: &mut SqliteConnection,
+    records: Vec<PersonIn>,
+    sync: bool,
+) -> Result<LoadReport, CoreError> {
+    let mut report = LoadReport::default();
+    let mut emails: Vec<String> = Vec::new();
+
+    for record in records {
+        let new_person: NewPerson = record.into();
+
+        if let Ok(existing_person) =
+            database::controller::query_person_by_email(connection, &new_person.email)
+        {
+            report
+                .changes
+                .extend(diff_person(&existing_person, &new_person));
+        }
+
+        let (db_person, created) = database::controller::add_person(connection, &new_person)?;
+        let person_balance = database::controller::query_balance_by_person(connection, &db_person)?;
 
-    for deserialize_result in rdr.deserialize() {
-        let record: PersonIn = deserialize_result.expect("Error converting data");
-        let (db_person, created) =
-            database::controller::add_person(&mut connection, &record.into())?;
+        emails.push(db_person.email.clone());
+        report
+            .people
+            .push(person_out(db_person, created, person_balance.value));
+    }
+
+    for mut existing_person in database::controller::list_people(connection)? {
+        if existing_person.is_internal()
+            || !existing_person.active
+            || emails.contains(&existing_person.email)
+        {
+            continue;
+        }
+
+        if sync {
+            existing_person =
+                database::controller::deactivate_person(connection, &existing_person)?;
+        }
 
         let person_balance =
-            database::controller::query_balance_by_person(&mut connection, &db_person)?;
+            database::controller::query_balance_by_person(connection, &existing_person)?;
+        report
+            .missing
+            .push(person_out(existing_person, false, person_balance.value));
+    }
 
-        result.push(PersonOut {
-            name: db_person.name,
-            dept: db_person.dept,
-            role: db_person.role,
-            email: db_person.email,
-            currency: db_person.currency,
-            created: created,
-            balance: person_balance.value,
-            value: 0_f32,
+    Ok(report)
+}
+
+fn diff_person(existing_person: &Person, new_person: &NewPerson) -> Vec<PersonChange> {
+    let mut changes: Vec<PersonChange> = [
+        ("name", &existing_person.name, &new_person.name),
+        ("role", &existing_person.role, &new_person.role),
+        ("dept", &existing_person.dept, &new_person.dept),
+        ("currency", &existing_person.currency, &new_person.currency),
+    ]
+    .iter()
+    .filter(|(_, old, new)| old != new)
+    .map(|(field, old, new)| PersonChange {
+        email: existing_person.email.clone(),
+        field: field.to_string(),
+        old: old.to_string(),
+        new: new.to_string(),
+    })
+    .collect();
+
+    if !existing_person.active {
+        changes.push(PersonChange {
+            email: existing_person.email.clone(),
+            field: "active".to_string(),
+            old: false.to_string(),
+            new: true.to_string(),
         });
     }
 
-    Ok(result)
+    changes
+}
+
+fn person_out(person: Person, created: bool, balance: i64) -> PersonOut {
+    PersonOut {
+        name: person.name,
+        dept: person.dept,
+        role: person.role,
+        email: person.email,
+        currency: person.currency,
+        created,
+        balance,
+        value: Some(Decimal::ZERO),
+    }
 }
 
 pub fn search(query: &HashMap<String, String>) -> Result<Vec<PersonOut>, CoreError> {
     let mut result: Vec<PersonOut> = Vec::new();
 
     let mut connection = database::connection::establish_connection()?;
-    let people = database::controller::query_person(&mut connection, &query)?;
-    let rates = get_rates(database::controller::get_currencies(&mut connection)?)?;
+    let people: Vec<Person> = database::controller::query_person(&mut connection, query)?
+        .into_iter()
+        .filter(|person| !person.is_internal())
+        .collect();
 
-    for person in people {
-        if &person.name == "admin" {
-            continue;
-        }
+    let mut currencies: Vec<String> = people.iter().map(|p| p.currency.clone()).collect();
+    currencies.sort();
+    currencies.dedup();
+
+    let settings = config::exchange_settings();
+    let provider = configured_provider()?;
+    let exchange_rates = database::controller::get_exchange_rates(
+        &mut connection,
+        provider.as_ref(),
+        &settings.base_currency,
+        Duration::seconds(settings.ttl),
+        currencies,
+    )?;
+
+    for stale_rate in exchange_rates.stale {
+        eprintln!(
+            "Warning: could not refresh the {} rate ({}), using the one fetched at {}.",
+            stale_rate.currency, stale_rate.reason, stale_rate.fetched_at
+        );
+    }
+    for (currency, reason) in exchange_rates.failures {
+        eprintln!(
+            "Warning: no {} rate ({}), values in {} are not shown.",
+            currency, reason, currency
+        );
+    }
 
+    for person in people {
         let person_balance =
             database::controller::query_balance_by_person(&mut connection, &person)?;
 
-        let rate;
-
-        match rates.get(&person.currency) {
-            Some(r) => rate = r,
-            None => return Err(CoreError::Exchange(ExchangeError::NotFound)),
-        }
+        let value = exchange_rates.rates.get(&person.currency).map(|rate| {
+            round_to_minor_units(
+                points_to_decimal(person_balance.value) * rate.value,
+                &person.currency,
+            )
+        });
 
         result.push(PersonOut {
             name: person.name.clone(),
@@ -105,7 +334,7 @@ pub fn search(query: &HashMap<String, String>) -> Result<Vec<PersonOut>, CoreErr
             currency: person.currency.clone(),
             created: false,
             balance: person_balance.value,
-            value: rate.value.parse::<f32>().unwrap() * person_balance.value,
+            value,
         });
     }
 
@@ -113,28 +342,725 @@ pub fn search(query: &HashMap<String, String>) -> Result<Vec<PersonOut>, CoreErr
 }
 
 pub fn move_points(
-    value: f32,
-    actor: &String,
+    value: i64,
+    actor: &str,
     query: &HashMap<String, String>,
+    memo: &Option<String>,
 ) -> Result<Vec<PersonOut>, CoreError> {
-    let mut result: Vec<PersonOut> = Vec::new();
     let mut connection = database::connection::establish_connection()?;
+    grant_points(&mut connection, value, actor, query, memo)?;
 
-    let people = database::controller::query_person(&mut connection, query)?;
+    search(query)
+}
 
-    for person in &people {
-        database::controller::add_movement(&mut connection, person, value, Some(actor.clone()))?;
-        search(&query)?.iter().for_each(|p| result.push(p.clone()));
-    }
+/// Adds a movement of `value` for every person `query` reaches. Either all of them get
+/// it or, when one fails, none does.
+fn grant_points(
+    connection: &mut SqliteConnection,
+    value: i64,
+    actor: &str,
+    query: &HashMap<String, String>,
+    memo: &Option<String>,
+) -> Result<usize, CoreError> {
+    let kind = if value < 0 {
+        MovementKind::Revoke
+    } else {
+        MovementKind::Grant
+    };
 
-    Ok(result)
+    connection.transaction(|connection| {
+        let people = database::controller::query_grant_targets(connection, query)?;
+
+        for person in &people {
+            database::controller::add_movement(
+                connection,
+                person,
+                &NewMovement::new(person, value, actor, kind).with_memo(memo),
+            )?;
+        }
+
+        Ok(people.len())
+    })
 }
 
-pub fn get_statement(person_id: i32) -> Result<(Balance, Vec<Movement>), CoreError> {
+pub fn get_statement(
+    person_id: i32,
+    filter: &MovementFilter,
+) -> Result<(StatementSummary, Vec<MovementOut>), CoreError> {
     let mut connection = database::connection::establish_connection()?;
     let person = database::controller::query_person_by_id(&mut connection, person_id)?;
-    let movements = database::controller::list_movements(&mut connection, &person)?;
-    let balance = database::controller::query_balance_by_person(&mut connection, &person)?;
+    statement_of(&mut connection, &person, filter)
+}
+
+fn statement_of(
+    connection: &mut SqliteConnection,
+    person: &Person,
+    filter: &MovementFilter,
+) -> Result<(StatementSummary, Vec<MovementOut>), CoreError> {
+    let statement = database::controller::query_statement(connection, person, filter)?;
+
+    let mut counterparties: HashMap<i32, String> = HashMap::new();
+    let mut result: Vec<MovementOut> = Vec::new();
+
+    for (movement, balance) in statement.rows {
+        let counterparty = match movement.counterparty_person_id {
+            Some(counterparty_id) => match counterparties.get(&counterparty_id) {
+                Some(email) => Some(email.clone()),
+                None => {
+                    let counterparty_person =
+                        database::controller::query_person_by_id(connection, counterparty_id)?;
+                    counterparties.insert(counterparty_id, counterparty_person.email.clone());
+                    Some(counterparty_person.email)
+                }
+            },
+            None => None,
+        };
+
+        result.push(MovementOut {
+            person: person.email.clone(),
+            date: movement.date,
+            kind: movement.kind,
+            value: movement.value,
+            balance,
+            counterparty,
+            transfer_id: movement.transfer_id,
+            memo: movement.memo,
+            actor: movement.actor,
+        });
+    }
+
+    let summary = StatementSummary {
+        opening_balance: statement.opening_balance,
+        closing_balance: statement.closing_balance,
+        movements: statement.total,
+    };
+
+    Ok((summary, result))
+}
+
+/// Combined statement of everyone matching `query`, merged by date. Each row keeps the
+/// running balance of its own person and the summary adds up the balances of all of them.
+///
+/// A person asked for by email is included even when deactivated, so the ledger of a
+/// terminated employee can still be audited.
+pub fn get_combined_statement(
+    query: &HashMap<String, String>,
+    filter: &MovementFilter,
+) -> Result<(StatementSummary, Vec<MovementOut>), CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    combined_statement(&mut connection, query, filter)
+}
+
+fn combined_statement(
+    connection: &mut SqliteConnection,
+    query: &HashMap<String, String>,
+    filter: &MovementFilter,
+) -> Result<(StatementSummary, Vec<MovementOut>), CoreError> {
+    let people = match query.get("email") {
+        Some(email) => {
+            let person = database::controller::query_person_by_email(connection, email)?;
+            match query.get("dept") {
+                Some(dept) if dept != &person.dept => vec![],
+                _ => vec![person],
+            }
+        }
+        None => database::controller::query_person(connection, query)?,
+    };
+    let whole_period = MovementFilter {
+        order: SortOrder::Asc,
+        limit: None,
+        offset: 0,
+        ..filter.clone()
+    };
+
+    let mut summary = StatementSummary {
+        opening_balance: 0,
+        closing_balance: 0,
+        movements: 0,
+    };
+    let mut result: Vec<MovementOut> = Vec::new();
+
+    for person in people {
+        if person.is_internal() {
+            continue;
+        }
+
+        let (person_summary, movements) = statement_of(connection, &person, &whole_period)?;
+        summary.opening_balance += person_summary.opening_balance;
+        summary.closing_balance += person_summary.closing_balance;
+        result.extend(movements);
+    }
+
+    result.sort_by_key(|movement| movement.date);
+    if filter.order == SortOrder::Desc {
+        result.reverse();
+    }
+    summary.movements = result.len();
+
+    let result = result
+        .into_iter()
+        .skip(filter.offset)
+        .take(filter.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok((summary, result))
+}
+
+pub fn change_password(
+    user: &User,
+    new_password: &str,
+    confirmation: &str,
+) -> Result<User, CoreError> {
+    if new_password != confirmation {
+        return Err(CoreError::PasswordMismatch);
+    }
+
+    if let Err(violations) = check_password_strength(new_password, &config::password_policy()) {
+        return Err(CoreError::WeakPassword(violations));
+    }
+
+    let mut connection = database::connection::establish_connection()?;
+    Ok(database::controller::change_password(
+        &mut connection,
+        user,
+        new_password,
+    )?)
+}
+
+pub fn reset_password(manager: &User, email: &String) -> Result<(Person, User, String), CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let person = database::controller::query_person_by_email(&mut connection, email)?;
+    let existing_user = database::controller::query_user_by_person(&mut connection, &person)?;
+    database::controller::check_can_manage(&mut connection, manager, &existing_user)?;
+    let (user, temporary_password) =
+        database::controller::reset_password(&mut connection, &person)?;
+
+    Ok((person, user, temporary_password))
+}
+
+pub fn unlock_user(manager: &User, email: &String) -> Result<(Person, User), CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let person = database::controller::query_person_by_email(&mut connection, email)?;
+    let existing_user = database::controller::query_user_by_person(&mut connection, &person)?;
+    database::controller::check_can_manage(&mut connection, manager, &existing_user)?;
+    let user = database::controller::unlock_user(&mut connection, &existing_user)?;
+
+    Ok((person, user))
+}
+
+pub fn disable_two_factor(manager: &User, email: &String) -> Result<(Person, User), CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let person = database::controller::query_person_by_email(&mut connection, email)?;
+    let existing_user = database::controller::query_user_by_person(&mut connection, &person)?;
+    database::controller::check_can_manage(&mut connection, manager, &existing_user)?;
+    let user = database::controller::disable_totp(&mut connection, &existing_user)?;
+
+    Ok((person, user))
+}
+
+/// Changes the role of the user of `email`. `manager` must hold every permission of both
+/// the current and the new role, and the internal people and service accounts keep
+/// theirs.
+pub fn set_role(manager: &User, email: &String, role: &str) -> Result<(Person, User), CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    change_role(&mut connection, manager, email, role)
+}
 
-    Ok((balance, movements))
+fn change_role(
+    connection: &mut SqliteConnection,
+    manager: &User,
+    email: &String,
+    role: &str,
+) -> Result<(Person, User), CoreError> {
+    let person = database::controller::query_person_by_email(connection, email)?;
+    let existing_user = database::controller::query_user_by_person(connection, &person)?;
+
+    if person.is_internal() || existing_user.service {
+        return Err(ControllerError::RoleFixed(existing_user.username).into());
+    }
+    database::controller::check_can_manage(connection, manager, &existing_user)?;
+    database::controller::check_can_grant(connection, manager, role)?;
+
+    let user = database::controller::set_role(connection, &existing_user, role)?;
+
+    Ok((person, user))
+}
+
+pub fn list_sessions(all: bool) -> Result<Vec<SessionOut>, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let sessions =
+        database::controller::list_sessions(&mut connection, all, Utc::now().naive_utc())?;
+
+    Ok(sessions
+        .into_iter()
+        .map(|(session, user)| SessionOut {
+            id: session.id,
+            username: user.username,
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+            revoked_at: session.revoked_at,
+        })
+        .collect())
+}
+
+pub fn revoke_session(id: &str) -> Result<Session, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    Ok(database::controller::revoke_session(&mut connection, id)?)
+}
+
+pub fn create_service_account(name: &str) -> Result<User, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    Ok(database::controller::add_service_account(
+        &mut connection,
+        name,
+    )?)
+}
+
+/// Issues an API key for the service account `name`. Returns the key row and the key
+/// itself, which is not stored and cannot be shown again.
+pub fn issue_api_key(
+    name: &str,
+    scopes: &[Permission],
+    max_points: Option<i64>,
+    expires_at: NaiveDateTime,
+) -> Result<(ApiKey, String), CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let service_account = database::controller::query_service_account(&mut connection, name)?;
+
+    let generated = generate_api_key();
+    let api_key = database::controller::add_api_key(
+        &mut connection,
+        &NewApiKey {
+            id: generated.id,
+            user_id: service_account.id,
+            key_hash: hash_api_key(&generated.key),
+            scopes: scopes
+                .iter()
+                .map(|scope| scope.as_str())
+                .collect::<Vec<&str>>()
+                .join(","),
+            max_points,
+            created_at: Utc::now().naive_utc(),
+            expires_at,
+        },
+    )?;
+
+    Ok((api_key, generated.key))
+}
+
+pub fn list_api_keys() -> Result<Vec<ApiKeyOut>, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let api_keys = database::controller::list_api_keys(&mut connection)?;
+
+    Ok(api_keys
+        .into_iter()
+        .map(|(api_key, user)| ApiKeyOut {
+            id: api_key.id,
+            username: user.username,
+            scopes: api_key.scopes,
+            max_points: api_key.max_points.map(points_to_decimal),
+            created_at: api_key.created_at,
+            expires_at: api_key.expires_at,
+            last_used_at: api_key.last_used_at,
+            revoked_at: api_key.revoked_at,
+        })
+        .collect())
+}
+
+pub fn revoke_api_key(id: &str) -> Result<ApiKey, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    Ok(database::controller::revoke_api_key(&mut connection, id)?)
+}
+
+pub fn transfer_points(
+    sender: &Person,
+    actor: &str,
+    value: i64,
+    receiver_email: &String,
+    memo: &Option<String>,
+) -> Result<Person, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let (receiver, _, _) = database::controller::transfer_points(
+        &mut connection,
+        sender,
+        receiver_email,
+        value,
+        actor,
+        memo,
+    )?;
+
+    Ok(receiver)
+}
+
+/// Checks the ledger and, when `repair` is set, rebuilds every balance from the
+/// movement log. Returns the issues found before the repair, the number of balances
+/// rebuilt and the issues that are still there afterwards.
+pub fn verify_ledger(
+    repair: bool,
+) -> Result<(Vec<LedgerIssue>, usize, Vec<LedgerIssue>), CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let issues = database::controller::verify_ledger(&mut connection)?;
+
+    if !repair {
+        return Ok((issues.clone(), 0, issues));
+    }
+
+    let rebuilt = database::controller::rebuild_balances(&mut connection)?;
+    let remaining = database::controller::verify_ledger(&mut connection)?;
+
+    Ok((issues, rebuilt, remaining))
+}
+
+/// Refuses to go on when the database is missing migrations the binary was built with.
+pub fn check_schema() -> Result<(), CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let status = database::migrations::migration_status(&mut connection)?;
+
+    if !status.iter().any(|(_, applied)| *applied) {
+        return Err(CoreError::DatabaseNotInitialized);
+    }
+
+    let pending: Vec<String> = status
+        .into_iter()
+        .filter(|(_, applied)| !applied)
+        .map(|(name, _)| name)
+        .collect();
+    if !pending.is_empty() {
+        return Err(CoreError::SchemaOutdated(pending));
+    }
+
+    Ok(())
+}
+
+/// How far the schema is, for the credentials the schema commands ask for.
+pub enum SchemaState {
+    /// There is no `user` table yet, so nobody could log in.
+    WithoutUsers,
+    /// Migrations are pending, the current user model may not load.
+    Outdated,
+    Current,
+}
+
+pub fn schema_state() -> Result<SchemaState, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+
+    if !database::migrations::has_table(&mut connection, "user")? {
+        return Ok(SchemaState::WithoutUsers);
+    }
+    if database::migrations::pending_migrations(&mut connection)?.is_empty() {
+        Ok(SchemaState::Current)
+    } else {
+        Ok(SchemaState::Outdated)
+    }
+}
+
+/// Creates the schema of an empty database. Returns the applied migrations.
+pub fn init_database() -> Result<Vec<String>, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let status = database::migrations::migration_status(&mut connection)?;
+
+    if status.iter().any(|(_, applied)| *applied) {
+        return Err(CoreError::DatabaseAlreadyInitialized);
+    }
+
+    Ok(database::migrations::run_pending_migrations(
+        &mut connection,
+    )?)
+}
+
+pub fn migrate_database() -> Result<Vec<String>, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    Ok(database::migrations::run_pending_migrations(
+        &mut connection,
+    )?)
+}
+
+pub fn database_status() -> Result<Vec<MigrationOut>, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    let status = database::migrations::migration_status(&mut connection)?;
+
+    Ok(status
+        .into_iter()
+        .map(|(name, applied)| MigrationOut { name, applied })
+        .collect())
+}
+
+pub fn rollback_database(steps: usize) -> Result<Vec<String>, CoreError> {
+    let mut connection = database::connection::establish_connection()?;
+    Ok(database::migrations::revert_migrations(
+        &mut connection,
+        steps,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::connection::SimpleConnection;
+    use diesel::SqliteConnection;
+    use rstest::rstest;
+
+    use std::collections::HashMap;
+
+    use crate::core::{change_role, combined_statement, grant_points, load_records, CoreError};
+    use crate::database;
+    use crate::database::controller::test::{new_person, test_db_connection};
+    use crate::database::controller::ControllerError;
+    use crate::database::models::{MovementKind, NewMovement, NewPerson, User};
+    use crate::serializers::{MovementFilter, PersonIn, SortOrder};
+
+    fn person_in(email: &str, name: &str, dept: &str) -> PersonIn {
+        PersonIn {
+            name: name.to_string(),
+            dept: dept.to_string(),
+            role: "Salesman".to_string(),
+            email: email.to_string(),
+            currency: "USD".to_string(),
+        }
+    }
+
+    #[rstest]
+    fn load_is_all_or_nothing(mut test_db_connection: SqliteConnection) {
+        let records = vec![
+            person_in("jim@dm.com", "Jim Halpert", "Sales"),
+            person_in("not-an-email", "Dwight Schrute", "Sales"),
+        ];
+
+        assert!(load_records(&mut test_db_connection, records, false, false).is_err());
+        assert!(database::controller::query_person_by_email(
+            &mut test_db_connection,
+            &"jim@dm.com".to_string()
+        )
+        .is_err());
+    }
+
+    #[rstest]
+    fn dry_run_leaves_the_database_untouched(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        database::controller::add_person(&mut test_db_connection, &new_person).unwrap();
+        let records = vec![
+            person_in(&new_person.email, &new_person.name, "Accounting"),
+            person_in("jim@dm.com", "Jim Halpert", "Sales"),
+        ];
+
+        let report = load_records(&mut test_db_connection, records, true, true).unwrap();
+        assert_eq!(report.people.len(), 2);
+        assert_eq!(report.changes.len(), 1);
+
+        let existing_person =
+            database::controller::query_person_by_email(&mut test_db_connection, &new_person.email)
+                .unwrap();
+        assert_eq!(existing_person.dept, "Sales");
+        assert!(database::controller::query_person_by_email(
+            &mut test_db_connection,
+            &"jim@dm.com".to_string()
+        )
+        .is_err());
+    }
+
+    #[rstest]
+    fn load_reports_changes_and_missing_people(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        database::controller::add_person(&mut test_db_connection, &new_person).unwrap();
+        let records = vec![person_in("jim@dm.com", "Jim Halpert", "Sales")];
+        load_records(&mut test_db_connection, records, false, false).unwrap();
+
+        let mut changed = person_in("jim@dm.com", "Jim Halpert", "Sales");
+        changed.role = "Manager".to_string();
+        let report = load_records(&mut test_db_connection, vec![changed], false, true).unwrap();
+
+        assert_eq!(report.changes.len(), 1);
+        assert_eq!(report.changes[0].email, "jim@dm.com");
+        assert_eq!(report.changes[0].field, "role");
+        assert_eq!(report.changes[0].old, "Salesman");
+        assert_eq!(report.changes[0].new, "Manager");
+
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].email, new_person.email);
+        let missing_person =
+            database::controller::query_person_by_email(&mut test_db_connection, &new_person.email)
+                .unwrap();
+        assert!(!missing_person.active);
+    }
+
+    #[rstest]
+    fn combined_statement_includes_a_deactivated_person_by_email(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) =
+            database::controller::add_person(&mut test_db_connection, &new_person).unwrap();
+        database::controller::deactivate_person(&mut test_db_connection, &person).unwrap();
+        let filter = MovementFilter {
+            since: None,
+            until: None,
+            actor: None,
+            sign: None,
+            kind: None,
+            order: SortOrder::Asc,
+            limit: None,
+            offset: 0,
+        };
+
+        let by_email = HashMap::from([("email".to_string(), new_person.email.clone())]);
+        let (summary, movements) =
+            combined_statement(&mut test_db_connection, &by_email, &filter).unwrap();
+        assert_eq!(movements.len(), 1);
+        assert_eq!(summary.closing_balance, 50_000);
+
+        let by_dept = HashMap::from([("dept".to_string(), new_person.dept.clone())]);
+        let (_, movements) =
+            combined_statement(&mut test_db_connection, &by_dept, &filter).unwrap();
+        assert!(movements.is_empty());
+    }
+
+    /// A user whose role may change roles but otherwise only transfer points.
+    fn role_manager(connection: &mut SqliteConnection, new_person: &NewPerson) -> User {
+        connection
+            .batch_execute(
+                "INSERT INTO role (name, description) VALUES ('role_admin', 'Changes roles'); \
+                 INSERT INTO permission (role, name) VALUES \
+                 ('role_admin', 'roles.manage'), ('role_admin', 'points.transfer');",
+            )
+            .unwrap();
+        let (person, _) = database::controller::add_person(connection, new_person).unwrap();
+        let user = database::controller::query_user_by_person(connection, &person).unwrap();
+        database::controller::set_role(connection, &user, "role_admin").unwrap()
+    }
+
+    fn add_employee(connection: &mut SqliteConnection) -> String {
+        database::controller::add_person(
+            connection,
+            &NewPerson {
+                email: "jim@dm.com".to_string(),
+                name: "Jim Halpert".to_string(),
+                role: "Salesman".to_string(),
+                currency: "USD".to_string(),
+                dept: "Sales".to_string(),
+            },
+        )
+        .unwrap();
+        "jim@dm.com".to_string()
+    }
+
+    #[rstest]
+    fn roles_are_changed_within_the_callers_permissions(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let manager = role_manager(&mut test_db_connection, &new_person);
+        let email = add_employee(&mut test_db_connection);
+
+        let (_, user) = change_role(&mut test_db_connection, &manager, &email, "viewer").unwrap();
+        assert_eq!(user.role, "viewer");
+    }
+
+    #[rstest]
+    fn granting_a_role_beyond_the_callers_is_refused(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let manager = role_manager(&mut test_db_connection, &new_person);
+        let email = add_employee(&mut test_db_connection);
+
+        let result = change_role(&mut test_db_connection, &manager, &email, "finance");
+        assert!(matches!(
+            result,
+            Err(CoreError::Controller(ControllerError::RoleOutranks(_, _)))
+        ));
+
+        // Promoting themselves is no different.
+        let result = change_role(
+            &mut test_db_connection,
+            &manager,
+            &new_person.email,
+            "superadmin",
+        );
+        assert!(matches!(
+            result,
+            Err(CoreError::Controller(ControllerError::RoleOutranks(_, _)))
+        ));
+    }
+
+    #[rstest]
+    fn changing_the_role_of_a_higher_user_is_refused(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let manager = role_manager(&mut test_db_connection, &new_person);
+        let email = add_employee(&mut test_db_connection);
+        let person =
+            database::controller::query_person_by_email(&mut test_db_connection, &email).unwrap();
+        let user =
+            database::controller::query_user_by_person(&mut test_db_connection, &person).unwrap();
+        database::controller::set_role(&mut test_db_connection, &user, "finance").unwrap();
+
+        let result = change_role(&mut test_db_connection, &manager, &email, "viewer");
+        assert!(matches!(
+            result,
+            Err(CoreError::Controller(ControllerError::TargetOutranks(_, _)))
+        ));
+    }
+
+    #[rstest]
+    fn internal_and_service_roles_are_fixed(mut test_db_connection: SqliteConnection) {
+        let admin =
+            database::controller::user_exists(&mut test_db_connection, &"admin".to_string())
+                .unwrap();
+        database::controller::add_service_account(&mut test_db_connection, "payroll").unwrap();
+
+        for email in ["admin@admin.com", "system@system.com"] {
+            let result = change_role(
+                &mut test_db_connection,
+                &admin,
+                &email.to_string(),
+                "viewer",
+            );
+            assert!(matches!(
+                result,
+                Err(CoreError::Controller(ControllerError::RoleFixed(_)))
+            ));
+        }
+    }
+
+    #[rstest]
+    fn grants_are_all_or_nothing(mut test_db_connection: SqliteConnection, new_person: NewPerson) {
+        let (first, _) =
+            database::controller::add_person(&mut test_db_connection, &new_person).unwrap();
+        add_employee(&mut test_db_connection);
+        let mut query = HashMap::new();
+        query.insert("dept".to_string(), "Sales".to_string());
+
+        assert_eq!(
+            grant_points(&mut test_db_connection, 100, "admin", &query, &None).unwrap(),
+            2
+        );
+        let balance =
+            database::controller::query_balance_by_person(&mut test_db_connection, &first).unwrap();
+        database::controller::add_movement(
+            &mut test_db_connection,
+            &first,
+            &NewMovement::new(&first, 100, "admin", MovementKind::Grant),
+        )
+        .unwrap();
+
+        // The second person cannot cover it, so the first keeps their points too.
+        let result = grant_points(
+            &mut test_db_connection,
+            -(balance.value + 50),
+            "admin",
+            &query,
+            &None,
+        );
+        assert!(matches!(
+            result,
+            Err(CoreError::Controller(ControllerError::InsufficientBalance))
+        ));
+        let after =
+            database::controller::query_balance_by_person(&mut test_db_connection, &first).unwrap();
+        assert_eq!(after.value, balance.value + 100);
+    }
 }
@@ -1,4 +1,5 @@
 pub mod connection;
 pub mod controller;
+pub mod migrations;
 pub mod models;
 pub mod schema;
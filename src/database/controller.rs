@@ -1,26 +1,127 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 
+use argon2::password_hash::Error as PasswordHashError;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::prelude::*;
+use diesel::sql_types::Text;
+use rust_decimal::Decimal;
 use serde_valid::Validate;
 use slugify::slugify;
 
+use crate::database::migrations::has_table;
 use crate::database::models::{
-    Balance, Movement, NewBalance, NewMovement, NewPerson, NewUser, Person, User,
+    ApiKey, Balance, ExchangeRate, Movement, MovementKind, NewApiKey, NewBalance, NewExchangeRate,
+    NewLoginAttempt, NewMovement, NewPerson, NewRecoveryCode, NewSession, NewUser, Person, Role,
+    SchemaAdmin, Session, User, SYSTEM_EMAIL,
 };
+use crate::database::schema::api_key::dsl as api_key;
+use crate::database::schema::api_key::table as api_key_table;
 use crate::database::schema::balance::dsl as balance;
 use crate::database::schema::balance::table as balance_table;
+use crate::database::schema::exchange_rate::dsl as exchange_rate;
+use crate::database::schema::exchange_rate::table as exchange_rate_table;
+use crate::database::schema::login_attempt::table as login_attempt_table;
+use crate::database::schema::movement::dsl as movement;
 use crate::database::schema::movement::table as movement_table;
+use crate::database::schema::permission::dsl as permission;
+use crate::database::schema::permission::table as permission_table;
 use crate::database::schema::person::dsl as person;
 use crate::database::schema::person::table as person_table;
+use crate::database::schema::recovery_code::dsl as recovery_code;
+use crate::database::schema::recovery_code::table as recovery_code_table;
+use crate::database::schema::role::table as role_table;
+use crate::database::schema::session::dsl as session;
+use crate::database::schema::session::table as session_table;
 use crate::database::schema::user::dsl as user;
 use crate::database::schema::user::table as user_table;
-use crate::utils::user::generate_simple_password;
+use crate::security::hash_password;
+use crate::serializers::{MovementFilter, SortOrder};
+use crate::utils::exchange::{parse_rate, ExchangeError, ExchangeProvider, Rate};
+use crate::utils::points::{format_points, POINTS_SCALE};
+use crate::utils::user::{generate_simple_password, generate_temporary_password};
+
+pub const SYSTEM_ACTOR: &str = "system";
+pub const DEFAULT_ROLE: &str = "employee";
+pub const MANAGEMENT_ROLE: &str = "manager";
+pub const SERVICE_ROLE: &str = "service";
+/// Usernames a service account cannot take: its movements would be indistinguishable
+/// from the ones the application records itself or from the seeded admin.
+pub const RESERVED_USERNAMES: [&str; 2] = [SYSTEM_ACTOR, "admin"];
 
 #[derive(Debug)]
 pub enum ControllerError {
     Database(diesel::result::Error),
     Validation(serde_valid::validation::Errors),
     InsufficientBalance,
+    InvalidAmount,
+    SelfTransfer,
+    PersonNotFound(String),
+    PersonInactive(String),
+    RoleNotFound(String),
+    SessionNotFound(String),
+    ApiKeyNotFound(String),
+    ServiceAccountNotFound(String),
+    UsernameTaken(String),
+    UsernameReserved(String),
+    TargetOutranks(String, String),
+    RoleOutranks(String, String),
+    RoleFixed(String),
+    PasswordHash(PasswordHashError),
+    Exchange(ExchangeError),
+}
+
+impl fmt::Display for ControllerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControllerError::Database(error) => write!(f, "database error: {}", error),
+            ControllerError::Validation(errors) => write!(f, "invalid data: {}", errors),
+            ControllerError::InsufficientBalance => write!(f, "insufficient balance"),
+            ControllerError::InvalidAmount => write!(f, "the amount must be greater than zero"),
+            ControllerError::SelfTransfer => write!(f, "cannot transfer points to yourself"),
+            ControllerError::PersonNotFound(email) => write!(f, "no person with email {}", email),
+            ControllerError::PersonInactive(email) => write!(f, "{} is no longer active", email),
+            ControllerError::RoleNotFound(name) => write!(f, "no role named {}", name),
+            ControllerError::SessionNotFound(id) => write!(f, "no session with id {}", id),
+            ControllerError::ApiKeyNotFound(id) => write!(f, "no API key with id {}", id),
+            ControllerError::ServiceAccountNotFound(username) => {
+                write!(f, "no service account named {}", username)
+            }
+            ControllerError::TargetOutranks(username, permission) => write!(
+                f,
+                "{} holds the {} permission, which you do not have",
+                username, permission
+            ),
+            ControllerError::RoleOutranks(role, permission) => write!(
+                f,
+                "the {} role holds the {} permission, which you do not have",
+                role, permission
+            ),
+            ControllerError::RoleFixed(username) => {
+                write!(f, "the role of {} cannot be changed", username)
+            }
+            ControllerError::UsernameTaken(username) => {
+                write!(f, "the username {} is already taken", username)
+            }
+            ControllerError::UsernameReserved(username) => {
+                write!(f, "the username {} is reserved", username)
+            }
+            ControllerError::PasswordHash(error) => write!(f, "password hashing failed: {}", error),
+            ControllerError::Exchange(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for ControllerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ControllerError::Database(error) => Some(error),
+            ControllerError::Validation(errors) => Some(errors),
+            ControllerError::Exchange(error) => Some(error),
+            _ => None,
+        }
+    }
 }
 
 impl From<diesel::result::Error> for ControllerError {
@@ -35,6 +136,18 @@ impl From<serde_valid::validation::Errors> for ControllerError {
     }
 }
 
+impl From<PasswordHashError> for ControllerError {
+    fn from(value: PasswordHashError) -> Self {
+        Self::PasswordHash(value)
+    }
+}
+
+impl From<ExchangeError> for ControllerError {
+    fn from(value: ExchangeError) -> Self {
+        Self::Exchange(value)
+    }
+}
+
 pub fn query_person_by_id(
     connection: &mut SqliteConnection,
     person_id: i32,
@@ -58,6 +171,8 @@ pub fn add_person(
                     person::currency.eq(&new_person.currency),
                     person::dept.eq(&new_person.dept),
                     person::role.eq(&new_person.role),
+                    person::active.eq(true),
+                    person::terminated_at.eq(None::<NaiveDateTime>),
                 ))
                 .get_result::<Person>(connection)?;
 
@@ -80,24 +195,59 @@ pub fn query_person(
     connection: &mut SqliteConnection,
     query: &HashMap<String, String>,
 ) -> Result<Vec<Person>, ControllerError> {
-    let result;
-
-    if query.contains_key("email") && query.contains_key("dept") {
-        let filter = person::email
-            .eq(&query["email"])
-            .and(person::dept.eq(&query["dept"]));
-        result = person::person.filter(filter).load::<Person>(connection);
-    } else if query.contains_key("email") {
-        let filter = person::email.eq(&query["email"]);
-        result = person::person.filter(filter).load::<Person>(connection);
-    } else if query.contains_key("dept") {
-        let filter = person::dept.eq(&query["dept"]);
-        result = person::person.filter(filter).load::<Person>(connection);
-    } else {
-        result = person::person.load::<Person>(connection);
+    let mut filtered = person::person.filter(person::active.eq(true)).into_boxed();
+
+    if let Some(email) = query.get("email") {
+        filtered = filtered.filter(person::email.eq(email));
+    }
+    if let Some(dept) = query.get("dept") {
+        filtered = filtered.filter(person::dept.eq(dept));
     }
 
-    Ok(result?)
+    Ok(filtered.load::<Person>(connection)?)
+}
+
+/// The people `add` and `remove` reach: the active people `query` matches, without the
+/// internal ones, which hold no points of their own.
+pub fn query_grant_targets(
+    connection: &mut SqliteConnection,
+    query: &HashMap<String, String>,
+) -> Result<Vec<Person>, ControllerError> {
+    Ok(query_person(connection, query)?
+        .into_iter()
+        .filter(|person| !person.is_internal())
+        .collect())
+}
+
+/// Every person, including the deactivated ones.
+pub fn list_people(connection: &mut SqliteConnection) -> Result<Vec<Person>, ControllerError> {
+    Ok(person_table.load::<Person>(connection)?)
+}
+
+/// Marks a person as having left the company. Their user, balance and movements are
+/// kept for history, but they can no longer log in, show up in searches or receive points.
+pub fn deactivate_person(
+    connection: &mut SqliteConnection,
+    existing_person: &Person,
+) -> Result<Person, ControllerError> {
+    let now = Utc::now().naive_utc();
+
+    diesel::update(
+        session_table.filter(
+            session::user_id.eq_any(
+                user_table
+                    .filter(user::person_id.eq(existing_person.id))
+                    .select(user::id),
+            ),
+        ),
+    )
+    .filter(session::revoked_at.is_null())
+    .set(session::revoked_at.eq(now))
+    .execute(connection)?;
+
+    Ok(diesel::update(existing_person)
+        .set((person::active.eq(false), person::terminated_at.eq(now)))
+        .get_result::<Person>(connection)?)
 }
 
 pub fn query_balance_by_person(
@@ -109,15 +259,37 @@ pub fn query_balance_by_person(
         .first(connection)?)
 }
 
+pub fn query_person_by_email(
+    connection: &mut SqliteConnection,
+    search_email: &String,
+) -> Result<Person, ControllerError> {
+    Ok(person_table
+        .filter(person::email.eq(search_email))
+        .first::<Person>(connection)?)
+}
+
+pub fn query_user_by_id(
+    connection: &mut SqliteConnection,
+    user_id: i32,
+) -> Result<User, ControllerError> {
+    Ok(user_table.find(user_id).first::<User>(connection)?)
+}
+
+pub fn query_user_by_person(
+    connection: &mut SqliteConnection,
+    person: &Person,
+) -> Result<User, ControllerError> {
+    Ok(User::belonging_to(person)
+        .select(User::as_select())
+        .first(connection)?)
+}
+
 fn person_exists(connection: &mut SqliteConnection, search_email: &String) -> Option<Person> {
     let person_exists_result = person_table
         .filter(person::email.eq(search_email))
         .first::<Person>(connection);
 
-    match person_exists_result {
-        Ok(existing_person) => Some(existing_person),
-        Err(_) => None,
-    }
+    person_exists_result.ok()
 }
 
 pub fn user_exists(connection: &mut SqliteConnection, search_username: &String) -> Option<User> {
@@ -125,46 +297,51 @@ pub fn user_exists(connection: &mut SqliteConnection, search_username: &String)
         .filter(user::username.eq(search_username))
         .first::<User>(connection);
 
-    match user_exists_result {
-        Ok(existing_user) => Some(existing_user),
-        Err(_) => None,
-    }
+    user_exists_result.ok()
 }
 
 fn set_initial_balance(
     connection: &mut SqliteConnection,
     person: &Person,
 ) -> Result<Balance, ControllerError> {
-    let value: f32;
+    let value: i64 = if person.role == "Manager" {
+        100 * POINTS_SCALE
+    } else {
+        500 * POINTS_SCALE
+    };
 
-    if person.role == "Manager" {
-        value = 100.0;
+    add_movement(
+        connection,
+        person,
+        &NewMovement::new(person, value, SYSTEM_ACTOR, MovementKind::Initial),
+    )
+}
+
+/// The role a new person's user starts with. The migration that introduced roles maps
+/// existing users the same way.
+pub fn initial_role(dept: &str) -> &'static str {
+    if dept.to_lowercase() == "management" {
+        MANAGEMENT_ROLE
     } else {
-        value = 500.0;
+        DEFAULT_ROLE
     }
-
-    add_movement(connection, person, value, None)
 }
 
 fn set_initial_password(
     connection: &mut SqliteConnection,
     person: &Person,
 ) -> Result<User, ControllerError> {
-    let superuser;
-
-    if person.dept.to_lowercase() == "management" {
-        superuser = true;
-    } else {
-        superuser = false;
-    }
+    let role = initial_role(&person.dept);
 
     let new_user = diesel::insert_into(user_table)
         .values(
             &(NewUser {
                 person_id: person.id,
-                password: generate_simple_password(8_usize),
-                superuser: superuser,
+                password: hash_password(&generate_simple_password(8_usize))?,
                 username: slugify!(&person.name),
+                must_change_password: true,
+                role: role.to_string(),
+                service: false,
             }),
         )
         .get_result::<User>(connection)?;
@@ -172,41 +349,560 @@ fn set_initial_password(
     Ok(new_user)
 }
 
+pub fn update_password(
+    connection: &mut SqliteConnection,
+    user: &User,
+    password_hash: &str,
+) -> Result<User, ControllerError> {
+    Ok(diesel::update(user)
+        .set(user::password.eq(password_hash))
+        .get_result::<User>(connection)?)
+}
+
+pub fn change_password(
+    connection: &mut SqliteConnection,
+    user: &User,
+    new_password: &str,
+) -> Result<User, ControllerError> {
+    let updated_user = diesel::update(user)
+        .set((
+            user::password.eq(hash_password(new_password)?),
+            user::must_change_password.eq(false),
+        ))
+        .get_result::<User>(connection)?;
+    revoke_user_sessions(connection, user)?;
+
+    Ok(updated_user)
+}
+
+/// Replaces the password of the user linked to `person` with a freshly generated
+/// temporary one and flags it to be changed on the next login.
+///
+/// Returns the updated user together with the temporary password in clear, which
+/// is not stored anywhere and must be handed to the person right away.
+pub fn reset_password(
+    connection: &mut SqliteConnection,
+    person: &Person,
+) -> Result<(User, String), ControllerError> {
+    if !person.active {
+        return Err(ControllerError::PersonInactive(person.email.clone()));
+    }
+
+    let existing_user = query_user_by_person(connection, person)?;
+    let temporary_password = generate_temporary_password(12_usize);
+
+    let updated_user = diesel::update(&existing_user)
+        .set((
+            user::password.eq(hash_password(&temporary_password)?),
+            user::must_change_password.eq(true),
+        ))
+        .get_result::<User>(connection)?;
+    revoke_user_sessions(connection, &existing_user)?;
+
+    Ok((updated_user, temporary_password))
+}
+
+pub fn query_role(connection: &mut SqliteConnection, name: &str) -> Result<Role, ControllerError> {
+    match role_table.find(name).first::<Role>(connection) {
+        Ok(role) => Ok(role),
+        Err(diesel::result::Error::NotFound) => {
+            Err(ControllerError::RoleNotFound(name.to_string()))
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+pub fn list_roles(connection: &mut SqliteConnection) -> Result<Vec<Role>, ControllerError> {
+    Ok(role_table.load::<Role>(connection)?)
+}
+
+pub fn query_permissions(
+    connection: &mut SqliteConnection,
+    role_name: &str,
+) -> Result<Vec<String>, ControllerError> {
+    Ok(permission_table
+        .filter(permission::role.eq(role_name))
+        .select(permission::name)
+        .order(permission::name.asc())
+        .load::<String>(connection)?)
+}
+
+pub fn role_has_permission(
+    connection: &mut SqliteConnection,
+    role_name: &str,
+    permission_name: &str,
+) -> Result<bool, ControllerError> {
+    Ok(diesel::select(diesel::dsl::exists(
+        permission_table
+            .filter(permission::role.eq(role_name))
+            .filter(permission::name.eq(permission_name)),
+    ))
+    .get_result::<bool>(connection)?)
+}
+
+/// The password of `username` and whether it holds `permission`, on a schema that may
+/// be older than the binary. Schemas from before roles had a `superuser` flag instead,
+/// which stands in for every permission.
+pub fn query_schema_admin(
+    connection: &mut SqliteConnection,
+    username: &str,
+    permission_name: &str,
+) -> Result<Option<SchemaAdmin>, ControllerError> {
+    let schema_admin = if has_table(connection, "permission")? {
+        diesel::sql_query(
+            "SELECT user.password AS password, EXISTS (SELECT 1 FROM permission \
+             WHERE permission.role = user.role AND permission.name = ?) AS granted \
+             FROM user WHERE user.username = ?",
+        )
+        .bind::<Text, _>(permission_name)
+        .bind::<Text, _>(username)
+        .get_result::<SchemaAdmin>(connection)
+        .optional()?
+    } else {
+        diesel::sql_query("SELECT password, superuser AS granted FROM user WHERE username = ?")
+            .bind::<Text, _>(username)
+            .get_result::<SchemaAdmin>(connection)
+            .optional()?
+    };
+
+    Ok(schema_admin)
+}
+
+/// Refuses when the role of `target` holds a permission that the role of `manager` lacks,
+/// so that managing an account never hands over more than the manager already has.
+pub fn check_can_manage(
+    connection: &mut SqliteConnection,
+    manager: &User,
+    target: &User,
+) -> Result<(), ControllerError> {
+    match permission_beyond(connection, manager, &target.role)? {
+        Some(permission) => Err(ControllerError::TargetOutranks(
+            target.username.clone(),
+            permission,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Refuses when `role_name` holds a permission that the role of `manager` lacks, so that
+/// nobody hands out more than they have themselves.
+pub fn check_can_grant(
+    connection: &mut SqliteConnection,
+    manager: &User,
+    role_name: &str,
+) -> Result<(), ControllerError> {
+    let role = query_role(connection, role_name)?;
+
+    match permission_beyond(connection, manager, &role.name)? {
+        Some(permission) => Err(ControllerError::RoleOutranks(role.name, permission)),
+        None => Ok(()),
+    }
+}
+
+/// A permission of `role_name` that the role of `manager` does not hold, if any.
+fn permission_beyond(
+    connection: &mut SqliteConnection,
+    manager: &User,
+    role_name: &str,
+) -> Result<Option<String>, ControllerError> {
+    let manager_permissions = query_permissions(connection, &manager.role)?;
+
+    Ok(query_permissions(connection, role_name)?
+        .into_iter()
+        .find(|permission| !manager_permissions.contains(permission)))
+}
+
+pub fn set_role(
+    connection: &mut SqliteConnection,
+    existing_user: &User,
+    role_name: &str,
+) -> Result<User, ControllerError> {
+    let role = query_role(connection, role_name)?;
+
+    let updated_user = diesel::update(existing_user)
+        .set(user::role.eq(role.name))
+        .get_result::<User>(connection)?;
+    revoke_user_sessions(connection, existing_user)?;
+
+    Ok(updated_user)
+}
+
+/// Creates a service account, a user of the `system` person that cannot log in with a
+/// password and acts through API keys only.
+pub fn add_service_account(
+    connection: &mut SqliteConnection,
+    username: &str,
+) -> Result<User, ControllerError> {
+    if RESERVED_USERNAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(username.trim()))
+    {
+        return Err(ControllerError::UsernameReserved(username.to_string()));
+    }
+    if user_exists(connection, &username.to_string()).is_some() {
+        return Err(ControllerError::UsernameTaken(username.to_string()));
+    }
+
+    let system_person = person_table
+        .filter(person::email.eq(SYSTEM_EMAIL))
+        .first::<Person>(connection)?;
+
+    Ok(diesel::insert_into(user_table)
+        .values(&NewUser {
+            password: hash_password(&generate_simple_password(32_usize))?,
+            person_id: system_person.id,
+            username: username.to_string(),
+            must_change_password: false,
+            role: SERVICE_ROLE.to_string(),
+            service: true,
+        })
+        .get_result::<User>(connection)?)
+}
+
+pub fn query_service_account(
+    connection: &mut SqliteConnection,
+    username: &str,
+) -> Result<User, ControllerError> {
+    match user_table
+        .filter(user::username.eq(username))
+        .filter(user::service.eq(true))
+        .first::<User>(connection)
+    {
+        Ok(service_account) => Ok(service_account),
+        Err(diesel::result::Error::NotFound) => Err(ControllerError::ServiceAccountNotFound(
+            username.to_string(),
+        )),
+        Err(error) => Err(error.into()),
+    }
+}
+
+pub fn add_api_key(
+    connection: &mut SqliteConnection,
+    new_api_key: &NewApiKey,
+) -> Result<ApiKey, ControllerError> {
+    Ok(diesel::insert_into(api_key_table)
+        .values(new_api_key)
+        .get_result::<ApiKey>(connection)?)
+}
+
+pub fn query_api_key(
+    connection: &mut SqliteConnection,
+    id: &str,
+) -> Result<Option<ApiKey>, ControllerError> {
+    Ok(api_key_table
+        .find(id)
+        .first::<ApiKey>(connection)
+        .optional()?)
+}
+
+pub fn touch_api_key(
+    connection: &mut SqliteConnection,
+    existing_key: &ApiKey,
+    now: NaiveDateTime,
+) -> Result<ApiKey, ControllerError> {
+    Ok(diesel::update(existing_key)
+        .set(api_key::last_used_at.eq(Some(now)))
+        .get_result::<ApiKey>(connection)?)
+}
+
+/// API keys with their service account, newest first.
+pub fn list_api_keys(
+    connection: &mut SqliteConnection,
+) -> Result<Vec<(ApiKey, User)>, ControllerError> {
+    Ok(api_key_table
+        .inner_join(user_table)
+        .select((ApiKey::as_select(), User::as_select()))
+        .order(api_key::created_at.desc())
+        .load::<(ApiKey, User)>(connection)?)
+}
+
+pub fn revoke_api_key(
+    connection: &mut SqliteConnection,
+    id: &str,
+) -> Result<ApiKey, ControllerError> {
+    let existing_key = match query_api_key(connection, id)? {
+        Some(existing_key) => existing_key,
+        None => return Err(ControllerError::ApiKeyNotFound(id.to_string())),
+    };
+
+    if existing_key.revoked_at.is_some() {
+        return Ok(existing_key);
+    }
+
+    Ok(diesel::update(&existing_key)
+        .set(api_key::revoked_at.eq(Some(Utc::now().naive_utc())))
+        .get_result::<ApiKey>(connection)?)
+}
+
+pub fn add_login_attempt(
+    connection: &mut SqliteConnection,
+    username: &str,
+    success: bool,
+    attempted_at: NaiveDateTime,
+) -> Result<(), ControllerError> {
+    diesel::insert_into(login_attempt_table)
+        .values(&NewLoginAttempt {
+            username: username.to_string(),
+            attempted_at,
+            success,
+        })
+        .execute(connection)?;
+
+    Ok(())
+}
+
+/// Counts a failed login, locking the user until `now + lock_for` once `max_failures`
+/// are reached in a row. The counter starts over after a lock.
+pub fn register_failed_login(
+    connection: &mut SqliteConnection,
+    existing_user: &User,
+    max_failures: i32,
+    lock_for: Duration,
+    now: NaiveDateTime,
+) -> Result<User, ControllerError> {
+    connection.transaction(|connection| {
+        // Incremented in SQL so that concurrent failures are all counted.
+        let updated_user = diesel::update(existing_user)
+            .set(user::failed_logins.eq(user::failed_logins + 1))
+            .get_result::<User>(connection)?;
+
+        if max_failures <= 0 || updated_user.failed_logins < max_failures {
+            return Ok(updated_user);
+        }
+
+        Ok(diesel::update(existing_user)
+            .set((
+                user::failed_logins.eq(0),
+                user::locked_until.eq(Some(now + lock_for)),
+            ))
+            .get_result::<User>(connection)?)
+    })
+}
+
+/// Clears the failed login counter and any lock.
+pub fn unlock_user(
+    connection: &mut SqliteConnection,
+    existing_user: &User,
+) -> Result<User, ControllerError> {
+    Ok(diesel::update(existing_user)
+        .set((
+            user::failed_logins.eq(0),
+            user::locked_until.eq(None::<NaiveDateTime>),
+        ))
+        .get_result::<User>(connection)?)
+}
+
+/// Stores a new TOTP secret as pending. A secret already in use keeps working until
+/// [`enable_totp`] confirms the new one.
+pub fn set_totp_secret(
+    connection: &mut SqliteConnection,
+    existing_user: &User,
+    encrypted_secret: &str,
+) -> Result<User, ControllerError> {
+    Ok(diesel::update(existing_user)
+        .set(user::totp_pending_secret.eq(Some(encrypted_secret)))
+        .get_result::<User>(connection)?)
+}
+
+/// Turns two-factor authentication on with the pending secret and replaces the recovery
+/// codes. `step` is the time step of the code that confirmed the secret, recorded so the
+/// code cannot be used again to log in. Steps used with the previous secret no longer
+/// matter.
+pub fn enable_totp(
+    connection: &mut SqliteConnection,
+    existing_user: &User,
+    code_hashes: &[String],
+    step: i64,
+) -> Result<User, ControllerError> {
+    connection.transaction(|connection| {
+        diesel::delete(recovery_code_table.filter(recovery_code::user_id.eq(existing_user.id)))
+            .execute(connection)?;
+
+        let new_codes: Vec<NewRecoveryCode> = code_hashes
+            .iter()
+            .map(|code_hash| NewRecoveryCode {
+                user_id: existing_user.id,
+                code_hash: code_hash.clone(),
+            })
+            .collect();
+        diesel::insert_into(recovery_code_table)
+            .values(&new_codes)
+            .execute(connection)?;
+
+        Ok(diesel::update(existing_user)
+            .set((
+                user::totp_secret.eq(user::totp_pending_secret),
+                user::totp_pending_secret.eq(None::<String>),
+                user::totp_enabled.eq(true),
+                user::totp_last_step.eq(Some(step)),
+            ))
+            .get_result::<User>(connection)?)
+    })
+}
+
+pub fn disable_totp(
+    connection: &mut SqliteConnection,
+    existing_user: &User,
+) -> Result<User, ControllerError> {
+    connection.transaction(|connection| {
+        diesel::delete(recovery_code_table.filter(recovery_code::user_id.eq(existing_user.id)))
+            .execute(connection)?;
+
+        Ok(diesel::update(existing_user)
+            .set((
+                user::totp_secret.eq(None::<String>),
+                user::totp_pending_secret.eq(None::<String>),
+                user::totp_enabled.eq(false),
+            ))
+            .get_result::<User>(connection)?)
+    })
+}
+
+/// Records `step` as the last TOTP time step the user logged in with. Returns false,
+/// without changing anything, when that step or a later one was already used, so that
+/// an intercepted code cannot be replayed within its window (RFC 6238, section 5.2).
+pub fn use_totp_step(
+    connection: &mut SqliteConnection,
+    existing_user: &User,
+    step: i64,
+) -> Result<bool, ControllerError> {
+    let updated = diesel::update(
+        user_table.filter(user::id.eq(existing_user.id)).filter(
+            user::totp_last_step
+                .is_null()
+                .or(user::totp_last_step.lt(step)),
+        ),
+    )
+    .set(user::totp_last_step.eq(Some(step)))
+    .execute(connection)?;
+
+    Ok(updated > 0)
+}
+
+/// Marks an unused recovery code of the user as used. Returns whether there was one.
+pub fn use_recovery_code(
+    connection: &mut SqliteConnection,
+    existing_user: &User,
+    code_hash: &str,
+    now: NaiveDateTime,
+) -> Result<bool, ControllerError> {
+    let updated = diesel::update(
+        recovery_code_table
+            .filter(recovery_code::user_id.eq(existing_user.id))
+            .filter(recovery_code::code_hash.eq(code_hash))
+            .filter(recovery_code::used_at.is_null()),
+    )
+    .set(recovery_code::used_at.eq(Some(now)))
+    .execute(connection)?;
+
+    Ok(updated > 0)
+}
+
+pub fn add_session(
+    connection: &mut SqliteConnection,
+    new_session: &NewSession,
+) -> Result<Session, ControllerError> {
+    Ok(diesel::insert_into(session_table)
+        .values(new_session)
+        .get_result::<Session>(connection)?)
+}
+
+/// The session with `id` if it was neither revoked nor expired at `now`.
+pub fn query_active_session(
+    connection: &mut SqliteConnection,
+    id: &str,
+    now: NaiveDateTime,
+) -> Result<Option<Session>, ControllerError> {
+    Ok(session_table
+        .find(id)
+        .filter(session::revoked_at.is_null())
+        .filter(session::expires_at.gt(now))
+        .first::<Session>(connection)
+        .optional()?)
+}
+
+/// Sessions with their user, newest first. Unless `all` is set, revoked and expired
+/// sessions are left out.
+pub fn list_sessions(
+    connection: &mut SqliteConnection,
+    all: bool,
+    now: NaiveDateTime,
+) -> Result<Vec<(Session, User)>, ControllerError> {
+    let mut query = session_table
+        .inner_join(user_table)
+        .select((Session::as_select(), User::as_select()))
+        .order(session::created_at.desc())
+        .into_boxed();
+
+    if !all {
+        query = query
+            .filter(session::revoked_at.is_null())
+            .filter(session::expires_at.gt(now));
+    }
+
+    Ok(query.load::<(Session, User)>(connection)?)
+}
+
+pub fn revoke_session(
+    connection: &mut SqliteConnection,
+    id: &str,
+) -> Result<Session, ControllerError> {
+    let existing_session = match session_table.find(id).first::<Session>(connection) {
+        Ok(existing_session) => existing_session,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(ControllerError::SessionNotFound(id.to_string()))
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    if existing_session.revoked_at.is_some() {
+        return Ok(existing_session);
+    }
+
+    Ok(diesel::update(&existing_session)
+        .set(session::revoked_at.eq(Utc::now().naive_utc()))
+        .get_result::<Session>(connection)?)
+}
+
+/// Revokes every open session of `user`, so a changed credential or role also ends the
+/// sessions opened before it. Returns how many were revoked.
+pub fn revoke_user_sessions(
+    connection: &mut SqliteConnection,
+    user: &User,
+) -> Result<usize, ControllerError> {
+    Ok(diesel::update(
+        session_table
+            .filter(session::user_id.eq(user.id))
+            .filter(session::revoked_at.is_null()),
+    )
+    .set(session::revoked_at.eq(Utc::now().naive_utc()))
+    .execute(connection)?)
+}
+
 pub fn add_movement(
     connection: &mut SqliteConnection,
     person: &Person,
-    value: f32,
-    actor: Option<String>,
+    new_movement: &NewMovement,
 ) -> Result<Balance, ControllerError> {
-    let actor_str = actor.unwrap_or("system".to_string());
+    let value = new_movement.value;
 
-    match query_balance_by_person(connection, &person) {
-        Ok(existing_balance) => {
-            if existing_balance.value + value < 0.0 {
-                return Err(ControllerError::InsufficientBalance);
-            }
+    if let Ok(existing_balance) = query_balance_by_person(connection, person) {
+        if existing_balance.value + value < 0 {
+            return Err(ControllerError::InsufficientBalance);
         }
-        Err(_) => (),
     }
 
     diesel::insert_into(movement_table)
-        .values(
-            &(NewMovement {
-                person_id: person.id,
-                value: value,
-                actor: actor_str,
-            }),
-        )
+        .values(new_movement)
         .get_result::<Movement>(connection)?;
 
     let person_movements = Movement::belonging_to(&person)
         .select(Movement::as_select())
         .load(connection)?;
 
-    let mut total: f32 = 0.0;
-    person_movements.iter().for_each(|mov| total += mov.value);
+    let total: i64 = person_movements.iter().map(|mov| mov.value).sum();
 
-    match query_balance_by_person(connection, &person) {
+    match query_balance_by_person(connection, person) {
         Ok(existing_balance) => {
             let update_balance = diesel::update(&existing_balance)
                 .set((
@@ -221,7 +917,7 @@ pub fn add_movement(
                 .values(
                     &(NewBalance {
                         person_id: person.id,
-                        value: value,
+                        value,
                     }),
                 )
                 .get_result::<Balance>(connection)?;
@@ -230,6 +926,60 @@ pub fn add_movement(
     }
 }
 
+/// Moves `value` points from `sender` to the person with `receiver_email` in a single
+/// transaction, so either both movements are recorded or none is.
+///
+/// Returns the receiver together with the new balances of sender and receiver.
+pub fn transfer_points(
+    connection: &mut SqliteConnection,
+    sender: &Person,
+    receiver_email: &String,
+    value: i64,
+    actor: &str,
+    memo: &Option<String>,
+) -> Result<(Person, Balance, Balance), ControllerError> {
+    if value <= 0 {
+        return Err(ControllerError::InvalidAmount);
+    }
+
+    connection.transaction(|connection| {
+        let receiver = match person_exists(connection, receiver_email) {
+            Some(existing_person) => existing_person,
+            None => return Err(ControllerError::PersonNotFound(receiver_email.clone())),
+        };
+
+        if receiver.id == sender.id {
+            return Err(ControllerError::SelfTransfer);
+        }
+
+        if !receiver.active {
+            return Err(ControllerError::PersonInactive(receiver.email));
+        }
+
+        let transfer_id = generate_transfer_id();
+        let sender_balance = add_movement(
+            connection,
+            sender,
+            &NewMovement::new(sender, -value, actor, MovementKind::TransferOut)
+                .with_counterparty(&receiver, &transfer_id)
+                .with_memo(memo),
+        )?;
+        let receiver_balance = add_movement(
+            connection,
+            &receiver,
+            &NewMovement::new(&receiver, value, actor, MovementKind::TransferIn)
+                .with_counterparty(sender, &transfer_id)
+                .with_memo(memo),
+        )?;
+
+        Ok((receiver, sender_balance, receiver_balance))
+    })
+}
+
+fn generate_transfer_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
 pub fn list_movements(
     connection: &mut SqliteConnection,
     person: &Person,
@@ -239,41 +989,455 @@ pub fn list_movements(
         .load(connection)?)
 }
 
-pub fn get_currencies(connection: &mut SqliteConnection) -> Result<Vec<String>, ControllerError> {
-    let currencies = person::person
-        .select(person::currency)
-        .distinct()
-        .load::<String>(connection)?;
-    Ok(currencies)
+/// The rows of a statement, each with the balance right after it.
+pub struct StatementRows {
+    pub opening_balance: i64,
+    pub closing_balance: i64,
+    pub total: usize,
+    pub rows: Vec<(Movement, i64)>,
 }
 
-#[cfg(test)]
-mod test {
+/// Movements of `person` in the period of `filter`, oldest first unless the filter asks
+/// otherwise. The opening balance sums everything before `since`, and running balances
+/// are computed over every movement of the period, so hidden rows still count. `total`
+/// is the number of matching rows before `offset` and `limit` are applied.
+pub fn query_statement(
+    connection: &mut SqliteConnection,
+    person: &Person,
+    filter: &MovementFilter,
+) -> Result<StatementRows, ControllerError> {
+    let opening_balance: i64 = match filter.since {
+        Some(since) => Movement::belonging_to(person)
+            .filter(movement::date.lt(since))
+            .select(movement::value)
+            .load::<i64>(connection)?
+            .iter()
+            .sum(),
+        None => 0,
+    };
 
-    use std::env;
+    let mut period = Movement::belonging_to(person)
+        .select(Movement::as_select())
+        .order((movement::date.asc(), movement::id.asc()))
+        .into_boxed();
+    if let Some(since) = filter.since {
+        period = period.filter(movement::date.ge(since));
+    }
+    if let Some(until) = filter.until {
+        period = period.filter(movement::date.le(until));
+    }
 
-    use diesel::prelude::*;
-    use diesel_migrations::*;
-    use passwords::PasswordGenerator;
-    use rstest::{fixture, rstest};
+    let mut running_balance = opening_balance;
+    let mut rows: Vec<(Movement, i64)> = Vec::new();
 
-    use crate::database::controller::{add_person, person_exists};
-    use crate::database::models::NewPerson;
+    for period_movement in period.load::<Movement>(connection)? {
+        running_balance += period_movement.value;
+        if filter.matches(&period_movement) {
+            rows.push((period_movement, running_balance));
+        }
+    }
 
-    const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
-    const ID_GEN: PasswordGenerator = PasswordGenerator {
-        length: 8,
-        numbers: true,
-        lowercase_letters: true,
-        uppercase_letters: true,
-        symbols: false,
-        spaces: false,
-        exclude_similar_characters: false,
-        strict: true,
-    };
+    let total = rows.len();
+    if filter.order == SortOrder::Desc {
+        rows.reverse();
+    }
+
+    Ok(StatementRows {
+        opening_balance,
+        closing_balance: running_balance,
+        total,
+        rows: rows
+            .into_iter()
+            .skip(filter.offset)
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .collect(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LedgerIssue {
+    MissingBalance {
+        person_id: i32,
+    },
+    DuplicateBalance {
+        person_id: i32,
+        count: usize,
+    },
+    BalanceMismatch {
+        person_id: i32,
+        balance: i64,
+        movements: i64,
+    },
+    OrphanedBalance {
+        balance_id: i32,
+        person_id: i32,
+    },
+    OrphanedMovement {
+        movement_id: i32,
+        person_id: i32,
+    },
+    OrphanedUser {
+        user_id: i32,
+        person_id: i32,
+    },
+}
+
+impl LedgerIssue {
+    pub fn check(&self) -> &'static str {
+        match self {
+            LedgerIssue::MissingBalance { .. } => "missing_balance",
+            LedgerIssue::DuplicateBalance { .. } => "duplicate_balance",
+            LedgerIssue::BalanceMismatch { .. } => "balance_mismatch",
+            LedgerIssue::OrphanedBalance { .. } => "orphaned_balance",
+            LedgerIssue::OrphanedMovement { .. } => "orphaned_movement",
+            LedgerIssue::OrphanedUser { .. } => "orphaned_user",
+        }
+    }
+
+    pub fn detail(&self) -> String {
+        match self {
+            LedgerIssue::MissingBalance { person_id } => {
+                format!("person {} has no balance row", person_id)
+            }
+            LedgerIssue::DuplicateBalance { person_id, count } => {
+                format!("person {} has {} balance rows", person_id, count)
+            }
+            LedgerIssue::BalanceMismatch {
+                person_id,
+                balance,
+                movements,
+            } => format!(
+                "person {} balance is {} but movements add up to {}",
+                person_id,
+                format_points(*balance),
+                format_points(*movements)
+            ),
+            LedgerIssue::OrphanedBalance {
+                balance_id,
+                person_id,
+            } => format!(
+                "balance {} points to missing person {}",
+                balance_id, person_id
+            ),
+            LedgerIssue::OrphanedMovement {
+                movement_id,
+                person_id,
+            } => format!(
+                "movement {} points to missing person {}",
+                movement_id, person_id
+            ),
+            LedgerIssue::OrphanedUser { user_id, person_id } => {
+                format!("user {} points to missing person {}", user_id, person_id)
+            }
+        }
+    }
+}
+
+/// Cross-checks the `balance` cache against the `movement` log and looks for rows
+/// pointing to people that no longer exist.
+pub fn verify_ledger(
+    connection: &mut SqliteConnection,
+) -> Result<Vec<LedgerIssue>, ControllerError> {
+    let mut issues: Vec<LedgerIssue> = Vec::new();
+
+    let person_ids: Vec<i32> = person_table.select(person::id).load(connection)?;
+    let balances: Vec<Balance> = balance_table
+        .select(Balance::as_select())
+        .load(connection)?;
+    let movements: Vec<(i32, i32, i64)> = movement_table
+        .select((movement::id, movement::person_id, movement::value))
+        .load(connection)?;
+    let users: Vec<(i32, i32)> = user_table
+        .select((user::id, user::person_id))
+        .load(connection)?;
+
+    let mut movement_totals: HashMap<i32, i64> = HashMap::new();
+    for (movement_id, person_id, value) in &movements {
+        if person_ids.contains(person_id) {
+            *movement_totals.entry(*person_id).or_insert(0) += value;
+        } else {
+            issues.push(LedgerIssue::OrphanedMovement {
+                movement_id: *movement_id,
+                person_id: *person_id,
+            });
+        }
+    }
+
+    for person_id in &person_ids {
+        let person_balances: Vec<&Balance> = balances
+            .iter()
+            .filter(|existing_balance| existing_balance.person_id == *person_id)
+            .collect();
+        let total = movement_totals.get(person_id).copied().unwrap_or(0);
+
+        match person_balances.len() {
+            0 => issues.push(LedgerIssue::MissingBalance {
+                person_id: *person_id,
+            }),
+            1 => (),
+            count => issues.push(LedgerIssue::DuplicateBalance {
+                person_id: *person_id,
+                count,
+            }),
+        }
+
+        for existing_balance in person_balances {
+            if existing_balance.value != total {
+                issues.push(LedgerIssue::BalanceMismatch {
+                    person_id: *person_id,
+                    balance: existing_balance.value,
+                    movements: total,
+                });
+            }
+        }
+    }
+
+    for existing_balance in &balances {
+        if !person_ids.contains(&existing_balance.person_id) {
+            issues.push(LedgerIssue::OrphanedBalance {
+                balance_id: existing_balance.id,
+                person_id: existing_balance.person_id,
+            });
+        }
+    }
+
+    for (user_id, person_id) in &users {
+        if !person_ids.contains(person_id) {
+            issues.push(LedgerIssue::OrphanedUser {
+                user_id: *user_id,
+                person_id: *person_id,
+            });
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Throws away every balance row and writes exactly one per person, computed from
+/// the movement log, inside a single transaction. Returns the number of rows written.
+pub fn rebuild_balances(connection: &mut SqliteConnection) -> Result<usize, ControllerError> {
+    connection.transaction(|connection| {
+        let person_ids: Vec<i32> = person_table.select(person::id).load(connection)?;
+        let movements: Vec<(i32, i64)> = movement_table
+            .select((movement::person_id, movement::value))
+            .load(connection)?;
+
+        diesel::delete(balance_table).execute(connection)?;
+
+        let new_balances: Vec<NewBalance> = person_ids
+            .iter()
+            .map(|person_id| NewBalance {
+                person_id: *person_id,
+                value: movements
+                    .iter()
+                    .filter(|(movement_person_id, _)| movement_person_id == person_id)
+                    .map(|(_, value)| value)
+                    .sum(),
+            })
+            .collect();
+
+        Ok(diesel::insert_into(balance_table)
+            .values(&new_balances)
+            .execute(connection)?)
+    })
+}
+
+/// A cached rate that was used because the provider could not refresh it.
+#[derive(Debug)]
+pub struct StaleRate {
+    pub currency: String,
+    pub fetched_at: NaiveDateTime,
+    pub reason: String,
+}
+
+/// The outcome of [`get_exchange_rates`]. Currencies that were never fetched and could
+/// not be fetched now are left out of `rates` and listed in `failures` with the reason.
+#[derive(Debug, Default)]
+pub struct ExchangeRates {
+    pub rates: HashMap<String, Rate>,
+    pub stale: Vec<StaleRate>,
+    pub failures: Vec<(String, String)>,
+}
+
+pub fn query_latest_exchange_rate(
+    connection: &mut SqliteConnection,
+    base: &str,
+    currency: &str,
+) -> Result<Option<ExchangeRate>, ControllerError> {
+    Ok(exchange_rate_table
+        .filter(exchange_rate::base.eq(base))
+        .filter(exchange_rate::currency.eq(currency))
+        .order((exchange_rate::fetched_at.desc(), exchange_rate::id.desc()))
+        .first::<ExchangeRate>(connection)
+        .optional()?)
+}
+
+pub fn add_exchange_rate(
+    connection: &mut SqliteConnection,
+    new_exchange_rate: &NewExchangeRate,
+) -> Result<ExchangeRate, ControllerError> {
+    Ok(diesel::insert_into(exchange_rate_table)
+        .values(new_exchange_rate)
+        .get_result::<ExchangeRate>(connection)?)
+}
+
+/// Returns the rate from `base` to each of `currencies`, using the stored rates that
+/// are younger than `ttl`. Older rates are fetched from `provider` in a single lookup and
+/// stored, so the table keeps the history of every rate used. If the provider fails, the
+/// last stored rate is used and reported back as stale. A currency that was never
+/// fetched and cannot be fetched now does not fail the lookup, it is reported in
+/// `failures` instead.
+pub fn get_exchange_rates(
+    connection: &mut SqliteConnection,
+    provider: &dyn ExchangeProvider,
+    base: &str,
+    ttl: Duration,
+    currencies: Vec<String>,
+) -> Result<ExchangeRates, ControllerError> {
+    let mut result = ExchangeRates::default();
+    let mut expired: Vec<(String, Option<ExchangeRate>)> = Vec::new();
+    let now = Utc::now().naive_utc();
+
+    for currency in currencies {
+        if currency == base {
+            result
+                .rates
+                .insert(currency.clone(), Rate::new(base, &currency, Decimal::ONE));
+            continue;
+        }
+
+        match query_latest_exchange_rate(connection, base, &currency)? {
+            Some(cached_rate) if now - cached_rate.fetched_at < ttl => {
+                let rate = parse_rate(&currency, &cached_rate.rate)?;
+                result
+                    .rates
+                    .insert(currency.clone(), Rate::new(base, &currency, rate));
+            }
+            cached => expired.push((currency, cached)),
+        }
+    }
+
+    if expired.is_empty() {
+        return Ok(result);
+    }
+
+    let requested: Vec<String> = expired
+        .iter()
+        .map(|(currency, _)| currency.clone())
+        .collect();
+    let mut fetched = match provider.get_rates(base, &requested) {
+        Ok(fetched) => fetched,
+        Err(error) => {
+            let reason = error.to_string();
+            for (currency, cached) in expired {
+                match cached {
+                    Some(cached_rate) => use_stale_rate(&mut result, cached_rate, reason.clone())?,
+                    None => result.failures.push((currency, reason.clone())),
+                }
+            }
+            return Ok(result);
+        }
+    };
+
+    for (currency, cached) in expired {
+        let fetched_rate = fetched
+            .remove(&currency)
+            .unwrap_or_else(|| Err(ExchangeError::NotFound(currency.clone())));
+
+        match (fetched_rate, cached) {
+            (Ok(rate), _) => {
+                add_exchange_rate(
+                    connection,
+                    &NewExchangeRate {
+                        currency: currency.clone(),
+                        rate: rate.value.to_string(),
+                        fetched_at: now,
+                        source: provider.name().to_string(),
+                        base: base.to_string(),
+                    },
+                )?;
+                result.rates.insert(currency, rate);
+            }
+            (Err(error), Some(cached_rate)) => {
+                use_stale_rate(&mut result, cached_rate, error.to_string())?;
+            }
+            (Err(error), None) => result.failures.push((currency, error.to_string())),
+        }
+    }
+
+    Ok(result)
+}
+
+fn use_stale_rate(
+    result: &mut ExchangeRates,
+    cached_rate: ExchangeRate,
+    reason: String,
+) -> Result<(), ControllerError> {
+    let rate = parse_rate(&cached_rate.currency, &cached_rate.rate)?;
+
+    result.rates.insert(
+        cached_rate.currency.clone(),
+        Rate::new(&cached_rate.base, &cached_rate.currency, rate),
+    );
+    result.stale.push(StaleRate {
+        currency: cached_rate.currency,
+        fetched_at: cached_rate.fetched_at,
+        reason,
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+pub(crate) mod test {
+
+    use std::env;
+
+    use diesel::prelude::*;
+    use diesel_migrations::MigrationHarness;
+    use passwords::PasswordGenerator;
+    use rstest::{fixture, rstest};
+
+    use std::collections::HashMap;
+
+    use chrono::{Duration, Utc};
+    use rust_decimal::Decimal;
+
+    use crate::database::controller::{
+        add_api_key, add_movement, add_person, add_service_account, add_session, change_password,
+        check_can_manage, deactivate_person, disable_totp, enable_totp, get_exchange_rates,
+        list_api_keys, list_movements, list_sessions, person_exists, query_active_session,
+        query_api_key, query_balance_by_person, query_grant_targets, query_latest_exchange_rate,
+        query_person, query_person_by_id, query_service_account, query_statement,
+        query_user_by_person, rebuild_balances, register_failed_login, reset_password,
+        revoke_api_key, revoke_session, role_has_permission, set_role, set_totp_secret,
+        transfer_points, unlock_user, use_recovery_code, use_totp_step, user_exists, verify_ledger,
+        ControllerError, LedgerIssue, DEFAULT_ROLE, MANAGEMENT_ROLE,
+    };
+    use crate::database::migrations::MIGRATIONS;
+    use crate::database::models::{MovementKind, NewApiKey, NewMovement, NewPerson, NewSession};
+    use crate::database::schema::balance::dsl as balance;
+    use crate::database::schema::exchange_rate::table as exchange_rate_table;
+    use crate::database::schema::movement::dsl as movement;
+    use crate::security::verify_password;
+    use crate::serializers::{MovementFilter, Sign, SortOrder};
+    use crate::utils::date::{parse_since, parse_until};
+    use crate::utils::exchange::fixed::FixedProvider;
+
+    const ID_GEN: PasswordGenerator = PasswordGenerator {
+        length: 8,
+        numbers: true,
+        lowercase_letters: true,
+        uppercase_letters: true,
+        symbols: false,
+        spaces: false,
+        exclude_similar_characters: false,
+        strict: true,
+    };
 
     #[fixture]
-    fn new_person() -> NewPerson {
+    pub(crate) fn new_person() -> NewPerson {
         NewPerson {
             email: "john-doe@dm.com".to_string(),
             name: "John Doe".to_string(),
@@ -284,7 +1448,7 @@ mod test {
     }
 
     #[fixture]
-    fn test_db_connection() -> SqliteConnection {
+    pub(crate) fn test_db_connection() -> SqliteConnection {
         let database = format!("/tmp/dundie_rewards_{}.db", ID_GEN.generate_one().unwrap());
         env::set_var("DATABASE_URL", &database);
 
@@ -342,4 +1506,823 @@ mod test {
 
         assert_eq!(person.is_none(), true);
     }
+
+    #[rstest]
+    fn reset_password_flags_user(mut test_db_connection: SqliteConnection, new_person: NewPerson) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let (user, temporary_password) = reset_password(&mut test_db_connection, &person).unwrap();
+
+        assert!(user.must_change_password);
+        assert!(verify_password(&temporary_password, &user.password).unwrap());
+    }
+
+    #[rstest]
+    fn change_password_clears_flag(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        assert!(user.must_change_password);
+
+        let user = change_password(&mut test_db_connection, &user, "Dunder2023").unwrap();
+
+        assert!(!user.must_change_password);
+        assert!(verify_password("Dunder2023", &user.password).unwrap());
+    }
+
+    #[fixture]
+    fn other_person() -> NewPerson {
+        NewPerson {
+            email: "jane-doe@dm.com".to_string(),
+            name: "Jane Doe".to_string(),
+            role: "Salesman".to_string(),
+            currency: "USD".to_string(),
+            dept: "Sales".to_string(),
+        }
+    }
+
+    #[rstest]
+    fn positive_transfer_points(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+        other_person: NewPerson,
+    ) {
+        let (sender, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let (receiver, _) = add_person(&mut test_db_connection, &other_person).unwrap();
+
+        let (_, sender_balance, receiver_balance) = transfer_points(
+            &mut test_db_connection,
+            &sender,
+            &receiver.email,
+            10_000,
+            "john-doe",
+            &Some("thanks".to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(sender_balance.value, 40_000);
+        assert_eq!(receiver_balance.value, 60_000);
+
+        let sent = list_movements(&mut test_db_connection, &sender).unwrap();
+        let received = list_movements(&mut test_db_connection, &receiver).unwrap();
+
+        assert_eq!(sent[0].kind, MovementKind::Initial);
+        assert_eq!(sent[1].kind, MovementKind::TransferOut);
+        assert_eq!(sent[1].counterparty_person_id, Some(receiver.id));
+        assert_eq!(received[1].kind, MovementKind::TransferIn);
+        assert_eq!(received[1].counterparty_person_id, Some(sender.id));
+        assert_eq!(sent[1].transfer_id, received[1].transfer_id);
+        assert_eq!(received[1].memo, Some("thanks".to_string()));
+    }
+
+    #[rstest]
+    fn transfer_points_insufficient_balance_rolls_back(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+        other_person: NewPerson,
+    ) {
+        let (sender, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let (receiver, _) = add_person(&mut test_db_connection, &other_person).unwrap();
+
+        let result = transfer_points(
+            &mut test_db_connection,
+            &sender,
+            &receiver.email,
+            100_000,
+            "john-doe",
+            &None,
+        );
+
+        assert!(matches!(result, Err(ControllerError::InsufficientBalance)));
+        assert_eq!(
+            query_balance_by_person(&mut test_db_connection, &sender)
+                .unwrap()
+                .value,
+            50_000
+        );
+        assert_eq!(
+            list_movements(&mut test_db_connection, &receiver)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[rstest]
+    fn transfer_points_unknown_receiver(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (sender, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+
+        let result = transfer_points(
+            &mut test_db_connection,
+            &sender,
+            &"nobody@dm.com".to_string(),
+            100,
+            "john-doe",
+            &None,
+        );
+
+        assert!(matches!(result, Err(ControllerError::PersonNotFound(_))));
+        assert_eq!(
+            list_movements(&mut test_db_connection, &sender)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(-100)]
+    fn transfer_points_invalid_amount(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+        other_person: NewPerson,
+        #[case] value: i64,
+    ) {
+        let (sender, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let (receiver, _) = add_person(&mut test_db_connection, &other_person).unwrap();
+
+        let result = transfer_points(
+            &mut test_db_connection,
+            &sender,
+            &receiver.email,
+            value,
+            "john-doe",
+            &None,
+        );
+
+        assert!(matches!(result, Err(ControllerError::InvalidAmount)));
+    }
+
+    #[rstest]
+    fn transfer_points_to_self(mut test_db_connection: SqliteConnection, new_person: NewPerson) {
+        let (sender, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+
+        let result = transfer_points(
+            &mut test_db_connection,
+            &sender,
+            &sender.email,
+            100,
+            "john-doe",
+            &None,
+        );
+
+        assert!(matches!(result, Err(ControllerError::SelfTransfer)));
+    }
+
+    #[rstest]
+    fn deactivated_person_is_hidden_and_cannot_receive(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+        other_person: NewPerson,
+    ) {
+        let (sender, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let (receiver, _) = add_person(&mut test_db_connection, &other_person).unwrap();
+
+        let receiver = deactivate_person(&mut test_db_connection, &receiver).unwrap();
+        assert!(!receiver.active);
+        assert!(receiver.terminated_at.is_some());
+
+        let people = query_person(&mut test_db_connection, &HashMap::new()).unwrap();
+        assert!(people.iter().all(|person| person.id != receiver.id));
+
+        let result = transfer_points(
+            &mut test_db_connection,
+            &sender,
+            &receiver.email,
+            100,
+            "john-doe",
+            &None,
+        );
+        assert!(matches!(result, Err(ControllerError::PersonInactive(_))));
+
+        let history = list_movements(&mut test_db_connection, &receiver).unwrap();
+        assert!(!history.is_empty());
+    }
+
+    #[rstest]
+    fn new_users_get_a_role_from_their_dept(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        assert_eq!(user.role, DEFAULT_ROLE);
+
+        let manager = NewPerson {
+            email: "michael@dm.com".to_string(),
+            name: "Michael Scott".to_string(),
+            dept: "Management".to_string(),
+            ..new_person
+        };
+        let (person, _) = add_person(&mut test_db_connection, &manager).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        assert_eq!(user.role, MANAGEMENT_ROLE);
+    }
+
+    #[rstest]
+    #[case("hr_admin", "people.write", true)]
+    #[case("hr_admin", "points.grant", false)]
+    #[case("finance", "points.grant", true)]
+    #[case("finance", "people.write", false)]
+    #[case("viewer", "points.transfer", false)]
+    #[case("employee", "points.transfer", true)]
+    #[case("superadmin", "database.admin", true)]
+    fn role_permissions(
+        mut test_db_connection: SqliteConnection,
+        #[case] role: &str,
+        #[case] permission: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            role_has_permission(&mut test_db_connection, role, permission).unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    fn set_role_checks_the_role_exists(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+
+        let user = set_role(&mut test_db_connection, &user, "finance").unwrap();
+        assert_eq!(user.role, "finance");
+
+        let result = set_role(&mut test_db_connection, &user, "janitor");
+        assert!(matches!(result, Err(ControllerError::RoleNotFound(_))));
+    }
+
+    #[rstest]
+    fn hr_admin_cannot_manage_a_superadmin(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        let hr_admin = set_role(&mut test_db_connection, &user, "hr_admin").unwrap();
+        let superadmin = user_exists(&mut test_db_connection, &"admin".to_string()).unwrap();
+
+        let result = check_can_manage(&mut test_db_connection, &hr_admin, &superadmin);
+        assert!(matches!(result, Err(ControllerError::TargetOutranks(_, _))));
+
+        assert!(check_can_manage(&mut test_db_connection, &superadmin, &hr_admin).is_ok());
+        assert!(check_can_manage(&mut test_db_connection, &hr_admin, &hr_admin).is_ok());
+
+        let (person, _) = add_person(
+            &mut test_db_connection,
+            &NewPerson {
+                email: "jim@dm.com".to_string(),
+                name: "Jim Halpert".to_string(),
+                ..new_person
+            },
+        )
+        .unwrap();
+        let employee = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        assert!(check_can_manage(&mut test_db_connection, &hr_admin, &employee).is_ok());
+    }
+
+    #[rstest]
+    fn sessions_expire_and_can_be_revoked(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        let now = parse_since("2026-10-18T12:00:00").unwrap();
+
+        for (id, expires_at) in [
+            ("current", "2026-10-18T20:00:00"),
+            ("old", "2026-10-18T08:00:00"),
+        ] {
+            add_session(
+                &mut test_db_connection,
+                &NewSession {
+                    id: id.to_string(),
+                    user_id: user.id,
+                    created_at: parse_since("2026-10-18T00:00:00").unwrap(),
+                    expires_at: parse_since(expires_at).unwrap(),
+                },
+            )
+            .unwrap();
+        }
+
+        assert!(
+            query_active_session(&mut test_db_connection, "current", now)
+                .unwrap()
+                .is_some()
+        );
+        assert!(query_active_session(&mut test_db_connection, "old", now)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            list_sessions(&mut test_db_connection, false, now)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            list_sessions(&mut test_db_connection, true, now)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let session = revoke_session(&mut test_db_connection, "current").unwrap();
+        assert!(session.revoked_at.is_some());
+        assert!(
+            query_active_session(&mut test_db_connection, "current", now)
+                .unwrap()
+                .is_none()
+        );
+
+        let result = revoke_session(&mut test_db_connection, "missing");
+        assert!(matches!(result, Err(ControllerError::SessionNotFound(_))));
+    }
+
+    #[rstest]
+    fn service_accounts_belong_to_the_system_person(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let service_account = add_service_account(&mut test_db_connection, "payroll").unwrap();
+        let person =
+            query_person_by_id(&mut test_db_connection, service_account.person_id).unwrap();
+        assert!(service_account.service);
+        assert!(!service_account.must_change_password);
+        assert_eq!(service_account.role, "service");
+        assert!(person.is_internal());
+
+        let result = add_service_account(&mut test_db_connection, "payroll");
+        assert!(matches!(result, Err(ControllerError::UsernameTaken(_))));
+        let result = add_service_account(&mut test_db_connection, "System");
+        assert!(matches!(result, Err(ControllerError::UsernameReserved(_))));
+        let result = add_service_account(&mut test_db_connection, "admin");
+        assert!(matches!(result, Err(ControllerError::UsernameReserved(_))));
+
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        let result = query_service_account(&mut test_db_connection, &user.username);
+        assert!(matches!(
+            result,
+            Err(ControllerError::ServiceAccountNotFound(_))
+        ));
+    }
+
+    #[rstest]
+    fn people_named_like_the_internal_ones_are_regular(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(
+            &mut test_db_connection,
+            &NewPerson {
+                name: "system".to_string(),
+                ..new_person
+            },
+        )
+        .unwrap();
+        assert!(!person.is_internal());
+
+        let service_account = add_service_account(&mut test_db_connection, "payroll").unwrap();
+        assert_ne!(service_account.person_id, person.id);
+    }
+
+    #[rstest]
+    fn api_keys_can_be_revoked(mut test_db_connection: SqliteConnection) {
+        let service_account = add_service_account(&mut test_db_connection, "payroll").unwrap();
+        add_api_key(
+            &mut test_db_connection,
+            &NewApiKey {
+                id: "abcd1234".to_string(),
+                user_id: service_account.id,
+                key_hash: "hash".to_string(),
+                scopes: "points.grant".to_string(),
+                max_points: Some(5000),
+                created_at: parse_since("2026-10-18T00:00:00").unwrap(),
+                expires_at: parse_until("2027-01-31").unwrap(),
+            },
+        )
+        .unwrap();
+
+        let api_keys = list_api_keys(&mut test_db_connection).unwrap();
+        assert_eq!(api_keys.len(), 1);
+        assert_eq!(api_keys[0].1.username, "payroll");
+
+        let api_key = revoke_api_key(&mut test_db_connection, "abcd1234").unwrap();
+        assert!(api_key.revoked_at.is_some());
+        assert!(query_api_key(&mut test_db_connection, "abcd1234")
+            .unwrap()
+            .unwrap()
+            .revoked_at
+            .is_some());
+
+        let result = revoke_api_key(&mut test_db_connection, "missing");
+        assert!(matches!(result, Err(ControllerError::ApiKeyNotFound(_))));
+    }
+
+    #[rstest]
+    fn credential_changes_revoke_open_sessions(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        let now = Utc::now().naive_utc();
+        let open_session = |connection: &mut SqliteConnection, id: &str| {
+            add_session(
+                connection,
+                &NewSession {
+                    id: id.to_string(),
+                    user_id: user.id,
+                    created_at: now,
+                    expires_at: now + Duration::hours(8),
+                },
+            )
+            .unwrap();
+        };
+
+        open_session(&mut test_db_connection, "before-reset");
+        reset_password(&mut test_db_connection, &person).unwrap();
+        assert!(
+            query_active_session(&mut test_db_connection, "before-reset", now)
+                .unwrap()
+                .is_none()
+        );
+
+        open_session(&mut test_db_connection, "before-passwd");
+        change_password(&mut test_db_connection, &user, "An0ther-Secret").unwrap();
+        assert!(
+            query_active_session(&mut test_db_connection, "before-passwd", now)
+                .unwrap()
+                .is_none()
+        );
+
+        open_session(&mut test_db_connection, "before-deactivation");
+        deactivate_person(&mut test_db_connection, &person).unwrap();
+        assert!(
+            query_active_session(&mut test_db_connection, "before-deactivation", now)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[rstest]
+    fn failed_logins_lock_the_user(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let stale_user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        let now = parse_since("2026-10-18T12:00:00").unwrap();
+
+        // Both failures count even when read from the same, now outdated, row.
+        let mut user = stale_user.clone();
+        for _ in 0..2 {
+            user = register_failed_login(
+                &mut test_db_connection,
+                &stale_user,
+                3,
+                Duration::minutes(15),
+                now,
+            )
+            .unwrap();
+        }
+        assert_eq!(user.failed_logins, 2);
+        assert!(user.locked_until.is_none());
+
+        user = register_failed_login(
+            &mut test_db_connection,
+            &user,
+            3,
+            Duration::minutes(15),
+            now,
+        )
+        .unwrap();
+        assert_eq!(user.failed_logins, 0);
+        assert_eq!(user.locked_until, Some(now + Duration::minutes(15)));
+
+        user = unlock_user(&mut test_db_connection, &user).unwrap();
+        assert!(user.locked_until.is_none());
+    }
+
+    #[rstest]
+    fn enrolling_again_keeps_the_active_secret(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+
+        let user = set_totp_secret(&mut test_db_connection, &user, "first").unwrap();
+        let user = enable_totp(&mut test_db_connection, &user, &[], 100).unwrap();
+        assert!(user.totp_enabled);
+        assert_eq!(user.totp_secret.as_deref(), Some("first"));
+        assert!(user.totp_pending_secret.is_none());
+
+        let user = set_totp_secret(&mut test_db_connection, &user, "second").unwrap();
+        assert!(user.totp_enabled);
+        assert_eq!(user.totp_secret.as_deref(), Some("first"));
+        assert_eq!(user.totp_pending_secret.as_deref(), Some("second"));
+
+        let user = enable_totp(&mut test_db_connection, &user, &[], 90).unwrap();
+        assert_eq!(user.totp_secret.as_deref(), Some("second"));
+        assert!(user.totp_pending_secret.is_none());
+        assert_eq!(user.totp_last_step, Some(90));
+    }
+
+    #[rstest]
+    fn recovery_codes_work_once(mut test_db_connection: SqliteConnection, new_person: NewPerson) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        let now = parse_since("2026-10-18T12:00:00").unwrap();
+
+        let user = enable_totp(
+            &mut test_db_connection,
+            &user,
+            &["first".to_string(), "second".to_string()],
+            100,
+        )
+        .unwrap();
+        assert!(user.totp_enabled);
+
+        assert!(use_recovery_code(&mut test_db_connection, &user, "first", now).unwrap());
+        assert!(!use_recovery_code(&mut test_db_connection, &user, "first", now).unwrap());
+        assert!(!use_recovery_code(&mut test_db_connection, &user, "other", now).unwrap());
+
+        let user = disable_totp(&mut test_db_connection, &user).unwrap();
+        assert!(!user.totp_enabled);
+        assert!(!use_recovery_code(&mut test_db_connection, &user, "second", now).unwrap());
+    }
+
+    #[rstest]
+    fn totp_steps_are_used_once(mut test_db_connection: SqliteConnection, new_person: NewPerson) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+
+        assert!(use_totp_step(&mut test_db_connection, &user, 100).unwrap());
+        assert!(!use_totp_step(&mut test_db_connection, &user, 100).unwrap());
+        assert!(!use_totp_step(&mut test_db_connection, &user, 99).unwrap());
+        assert!(use_totp_step(&mut test_db_connection, &user, 101).unwrap());
+
+        let user = query_user_by_person(&mut test_db_connection, &person).unwrap();
+        assert_eq!(user.totp_last_step, Some(101));
+    }
+
+    #[rstest]
+    fn grants_skip_the_internal_people(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+
+        assert!(query_person(&mut test_db_connection, &HashMap::new())
+            .unwrap()
+            .iter()
+            .any(|person| person.is_internal()));
+        let targets = query_grant_targets(&mut test_db_connection, &HashMap::new()).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].id, person.id);
+    }
+
+    #[rstest]
+    fn add_person_reactivates(mut test_db_connection: SqliteConnection, new_person: NewPerson) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let _ = deactivate_person(&mut test_db_connection, &person).unwrap();
+
+        let (person, created) = add_person(&mut test_db_connection, &new_person).unwrap();
+
+        assert!(!created);
+        assert!(person.active);
+        assert!(person.terminated_at.is_none());
+    }
+
+    #[rstest]
+    fn verify_ledger_consistent(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+        other_person: NewPerson,
+    ) {
+        let _ = add_person(&mut test_db_connection, &new_person).unwrap();
+        let _ = add_person(&mut test_db_connection, &other_person).unwrap();
+
+        assert!(verify_ledger(&mut test_db_connection).unwrap().is_empty());
+    }
+
+    #[rstest]
+    fn verify_ledger_reports_and_repairs(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+        other_person: NewPerson,
+    ) {
+        let (drifted, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        let (missing, _) = add_person(&mut test_db_connection, &other_person).unwrap();
+
+        diesel::update(balance::balance.filter(balance::person_id.eq(drifted.id)))
+            .set(balance::value.eq(1))
+            .execute(&mut test_db_connection)
+            .unwrap();
+        diesel::delete(balance::balance.filter(balance::person_id.eq(missing.id)))
+            .execute(&mut test_db_connection)
+            .unwrap();
+
+        let issues = verify_ledger(&mut test_db_connection).unwrap();
+
+        assert!(issues.contains(&LedgerIssue::BalanceMismatch {
+            person_id: drifted.id,
+            balance: 1,
+            movements: 50_000,
+        }));
+        assert!(issues.contains(&LedgerIssue::MissingBalance {
+            person_id: missing.id
+        }));
+
+        rebuild_balances(&mut test_db_connection).unwrap();
+
+        assert!(verify_ledger(&mut test_db_connection).unwrap().is_empty());
+        assert_eq!(
+            query_balance_by_person(&mut test_db_connection, &missing)
+                .unwrap()
+                .value,
+            50_000
+        );
+    }
+
+    #[rstest]
+    fn exchange_rates_are_cached(mut test_db_connection: SqliteConnection) {
+        let currencies = vec!["USD".to_string(), "EUR".to_string()];
+        let first = FixedProvider::parse("EUR=0.92").unwrap();
+        let second = FixedProvider::parse("EUR=0.95").unwrap();
+
+        let rates = get_exchange_rates(
+            &mut test_db_connection,
+            &first,
+            "USD",
+            Duration::hours(1),
+            currencies.clone(),
+        )
+        .unwrap()
+        .rates;
+        assert_eq!(rates["USD"].value, Decimal::ONE);
+        assert_eq!(rates["EUR"].value.to_string(), "0.92");
+
+        let rates = get_exchange_rates(
+            &mut test_db_connection,
+            &second,
+            "USD",
+            Duration::hours(1),
+            currencies.clone(),
+        )
+        .unwrap()
+        .rates;
+        assert_eq!(rates["EUR"].value.to_string(), "0.92");
+
+        let rates = get_exchange_rates(
+            &mut test_db_connection,
+            &second,
+            "USD",
+            Duration::zero(),
+            currencies,
+        )
+        .unwrap()
+        .rates;
+        assert_eq!(rates["EUR"].value.to_string(), "0.95");
+
+        let stored: i64 = exchange_rate_table
+            .count()
+            .get_result(&mut test_db_connection)
+            .unwrap();
+        assert_eq!(stored, 2);
+    }
+
+    #[rstest]
+    fn exchange_rates_fall_back_to_stale(mut test_db_connection: SqliteConnection) {
+        let working = FixedProvider::parse("EUR=0.92").unwrap();
+        let failing = FixedProvider::parse("").unwrap();
+
+        let exchange_rates = get_exchange_rates(
+            &mut test_db_connection,
+            &failing,
+            "USD",
+            Duration::zero(),
+            vec!["EUR".to_string()],
+        )
+        .unwrap();
+        assert!(exchange_rates.rates.is_empty());
+        assert_eq!(exchange_rates.failures.len(), 1);
+
+        let _ = get_exchange_rates(
+            &mut test_db_connection,
+            &working,
+            "USD",
+            Duration::zero(),
+            vec!["EUR".to_string()],
+        )
+        .unwrap();
+
+        let exchange_rates = get_exchange_rates(
+            &mut test_db_connection,
+            &failing,
+            "USD",
+            Duration::zero(),
+            vec!["EUR".to_string(), "GBP".to_string()],
+        )
+        .unwrap();
+        assert_eq!(exchange_rates.rates["EUR"].value.to_string(), "0.92");
+        assert_eq!(exchange_rates.stale.len(), 1);
+        assert_eq!(exchange_rates.stale[0].currency, "EUR");
+        assert_eq!(exchange_rates.failures.len(), 1);
+        assert_eq!(exchange_rates.failures[0].0, "GBP");
+    }
+
+    #[rstest]
+    fn exchange_rates_skip_each_failed_currency(mut test_db_connection: SqliteConnection) {
+        let provider = FixedProvider::parse("EUR=0.92").unwrap();
+        let currencies = vec!["EUR".to_string(), "GBP".to_string(), "XYZ".to_string()];
+
+        let exchange_rates = get_exchange_rates(
+            &mut test_db_connection,
+            &provider,
+            "USD",
+            Duration::hours(1),
+            currencies,
+        )
+        .unwrap();
+
+        assert_eq!(exchange_rates.rates["EUR"].value.to_string(), "0.92");
+        let failed: Vec<&str> = exchange_rates
+            .failures
+            .iter()
+            .map(|(c, _)| c.as_str())
+            .collect();
+        assert_eq!(failed, vec!["GBP", "XYZ"]);
+
+        let stored = query_latest_exchange_rate(&mut test_db_connection, "USD", "EUR").unwrap();
+        assert_eq!(stored.unwrap().rate, "0.92");
+    }
+
+    #[rstest]
+    fn statement_period_and_filters(
+        mut test_db_connection: SqliteConnection,
+        new_person: NewPerson,
+    ) {
+        let (person, _) = add_person(&mut test_db_connection, &new_person).unwrap();
+        for (value, kind) in [
+            (1000, MovementKind::Grant),
+            (-300, MovementKind::Revoke),
+            (200, MovementKind::Grant),
+        ] {
+            let _ = add_movement(
+                &mut test_db_connection,
+                &person,
+                &NewMovement::new(&person, value, "admin", kind),
+            )
+            .unwrap();
+        }
+
+        let movements = list_movements(&mut test_db_connection, &person).unwrap();
+        for (month, existing) in movements.iter().enumerate() {
+            diesel::update(movement::movement.filter(movement::id.eq(existing.id)))
+                .set(movement::date.eq(parse_since(&format!("2023-0{}-01", month + 1)).unwrap()))
+                .execute(&mut test_db_connection)
+                .unwrap();
+        }
+
+        let period = MovementFilter {
+            since: Some(parse_since("2023-02-01").unwrap()),
+            until: Some(parse_until("2023-03-01").unwrap()),
+            ..MovementFilter::default()
+        };
+        let statement = query_statement(&mut test_db_connection, &person, &period).unwrap();
+        let rows: Vec<(i64, i64)> = statement.rows.iter().map(|(m, b)| (m.value, *b)).collect();
+        assert_eq!(statement.opening_balance, 50000);
+        assert_eq!(rows, vec![(1000, 51000), (-300, 50700)]);
+        assert_eq!(statement.closing_balance, 50700);
+
+        let debits = MovementFilter {
+            sign: Some(Sign::Debit),
+            ..MovementFilter::default()
+        };
+        let statement = query_statement(&mut test_db_connection, &person, &debits).unwrap();
+        let rows: Vec<(i64, i64)> = statement.rows.iter().map(|(m, b)| (m.value, *b)).collect();
+        assert_eq!(rows, vec![(-300, 50700)]);
+        assert_eq!(statement.closing_balance, 50900);
+
+        let page = MovementFilter {
+            order: SortOrder::Desc,
+            limit: Some(2),
+            offset: 1,
+            ..MovementFilter::default()
+        };
+        let statement = query_statement(&mut test_db_connection, &person, &page).unwrap();
+        let rows: Vec<(i64, i64)> = statement.rows.iter().map(|(m, b)| (m.value, *b)).collect();
+        assert_eq!(rows, vec![(-300, 50700), (1000, 51000)]);
+        assert_eq!(statement.total, 4);
+    }
 }
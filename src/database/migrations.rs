@@ -0,0 +1,167 @@
+use std::error::Error as StdError;
+
+use diesel::migration::MigrationSource;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use diesel::sqlite::Sqlite;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+/// The migrations in `migrations/`, built into the binary.
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+pub type MigrationError = Box<dyn StdError + Send + Sync>;
+
+#[derive(QueryableByName)]
+struct TableCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Whether the database has a table called `name`, whatever migrations were applied.
+pub fn has_table(
+    connection: &mut SqliteConnection,
+    name: &str,
+) -> Result<bool, diesel::result::Error> {
+    let tables = diesel::sql_query(
+        "SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = ?",
+    )
+    .bind::<Text, _>(name)
+    .get_result::<TableCount>(connection)?;
+
+    Ok(tables.count > 0)
+}
+
+/// Every migration the binary knows, oldest first, and whether it was applied.
+pub fn migration_status(
+    connection: &mut SqliteConnection,
+) -> Result<Vec<(String, bool)>, MigrationError> {
+    let applied = connection.applied_migrations()?;
+    let mut migrations = MigrationSource::<Sqlite>::migrations(&MIGRATIONS)?;
+    migrations.sort_by_key(|migration| migration.name().version().as_owned());
+
+    Ok(migrations
+        .iter()
+        .map(|migration| {
+            let name = migration.name();
+            (name.to_string(), applied.contains(&name.version()))
+        })
+        .collect())
+}
+
+pub fn pending_migrations(
+    connection: &mut SqliteConnection,
+) -> Result<Vec<String>, MigrationError> {
+    Ok(migration_status(connection)?
+        .into_iter()
+        .filter(|(_, applied)| !applied)
+        .map(|(name, _)| name)
+        .collect())
+}
+
+/// Applies the pending migrations and returns their names.
+pub fn run_pending_migrations(
+    connection: &mut SqliteConnection,
+) -> Result<Vec<String>, MigrationError> {
+    let pending = pending_migrations(connection)?;
+    connection.run_pending_migrations(MIGRATIONS)?;
+    Ok(pending)
+}
+
+/// Reverts the last `steps` applied migrations, newest first, and returns their names.
+pub fn revert_migrations(
+    connection: &mut SqliteConnection,
+    steps: usize,
+) -> Result<Vec<String>, MigrationError> {
+    let mut applied: Vec<String> = migration_status(connection)?
+        .into_iter()
+        .filter(|(_, applied)| *applied)
+        .map(|(name, _)| name)
+        .collect();
+    applied.reverse();
+    applied.truncate(steps);
+
+    for _ in 0..applied.len() {
+        connection.revert_last_migration(MIGRATIONS)?;
+    }
+
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::connection::SimpleConnection;
+    use diesel::prelude::*;
+    use rstest::rstest;
+
+    use crate::database::controller::initial_role;
+    use crate::database::migrations::{
+        has_table, migration_status, pending_migrations, revert_migrations, run_pending_migrations,
+    };
+    use crate::database::schema::user::dsl as user;
+    use crate::database::schema::user::table as user_table;
+
+    #[rstest]
+    fn migrate_and_roll_back() {
+        let mut connection = SqliteConnection::establish(":memory:").unwrap();
+        let total = pending_migrations(&mut connection).unwrap().len();
+        assert!(total > 0);
+        assert!(!has_table(&mut connection, "user").unwrap());
+
+        assert_eq!(
+            run_pending_migrations(&mut connection).unwrap().len(),
+            total
+        );
+        assert!(pending_migrations(&mut connection).unwrap().is_empty());
+        assert!(has_table(&mut connection, "user").unwrap());
+
+        let status = migration_status(&mut connection).unwrap();
+        let reverted = revert_migrations(&mut connection, 2).unwrap();
+        assert_eq!(
+            reverted,
+            vec![status[total - 1].0.clone(), status[total - 2].0.clone()]
+        );
+        let mut expected = reverted.clone();
+        expected.reverse();
+        assert_eq!(pending_migrations(&mut connection).unwrap(), expected);
+
+        assert_eq!(run_pending_migrations(&mut connection).unwrap().len(), 2);
+    }
+
+    #[rstest]
+    fn superusers_get_the_role_new_people_would() {
+        let mut connection = SqliteConnection::establish(":memory:").unwrap();
+        run_pending_migrations(&mut connection).unwrap();
+        let since_roles = migration_status(&mut connection)
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| name.as_str() >= "2026-10-18-160000")
+            .count();
+        revert_migrations(&mut connection, since_roles).unwrap();
+
+        connection
+            .batch_execute(
+                "INSERT INTO person (email, name, role, dept, currency) VALUES \
+                 ('michael@dm.com', 'Michael Scott', 'Manager', 'Management', 'USD'); \
+                 INSERT INTO user (person_id, username, password, superuser) VALUES \
+                 ((SELECT id FROM person WHERE email = 'michael@dm.com'), 'michael', 'x', 1);",
+            )
+            .unwrap();
+        run_pending_migrations(&mut connection).unwrap();
+
+        let roles: Vec<(String, String)> = user_table
+            .select((user::username, user::role))
+            .order(user::username)
+            .load(&mut connection)
+            .unwrap();
+        assert_eq!(
+            roles,
+            vec![
+                ("admin".to_string(), "superadmin".to_string()),
+                (
+                    "michael".to_string(),
+                    initial_role("Management").to_string()
+                ),
+            ]
+        );
+    }
+}
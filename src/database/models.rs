@@ -1,10 +1,25 @@
+use std::fmt;
+use std::str::FromStr;
+
 use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
 use diesel::prelude::*;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::{Bool, Text};
+use diesel::sqlite::{Sqlite, SqliteValue};
+use serde::{Deserialize, Serialize};
 use serde_valid::Validate;
 
+use crate::database::schema::api_key;
 use crate::database::schema::balance;
+use crate::database::schema::exchange_rate;
+use crate::database::schema::login_attempt;
 use crate::database::schema::movement;
 use crate::database::schema::person;
+use crate::database::schema::recovery_code;
+use crate::database::schema::role;
+use crate::database::schema::session;
 use crate::database::schema::user;
 use crate::utils::email::email_validator;
 
@@ -17,6 +32,22 @@ pub struct Person {
     pub role: String,
     pub currency: String,
     pub dept: String,
+    pub active: bool,
+    pub terminated_at: Option<NaiveDateTime>,
+}
+
+/// Email of the person seeded for the `admin` user.
+pub const ADMIN_EMAIL: &str = "admin@admin.com";
+/// Email of the person the service accounts belong to.
+pub const SYSTEM_EMAIL: &str = "system@system.com";
+
+impl Person {
+    /// The seeded `admin` person and the `system` person of the service accounts, left
+    /// out of listings and imports. They are told apart by their seeded email, names are
+    /// free to collide.
+    pub fn is_internal(&self) -> bool {
+        self.email == ADMIN_EMAIL || self.email == SYSTEM_EMAIL
+    }
 }
 
 #[derive(Insertable, Validate)]
@@ -36,14 +67,75 @@ pub struct NewPerson {
 pub struct Balance {
     pub id: i32,
     pub person_id: i32,
-    pub value: f32,
+    pub value: i64,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = balance)]
 pub struct NewBalance {
     pub person_id: i32,
-    pub value: f32,
+    pub value: i64,
+}
+
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementKind {
+    Initial,
+    Grant,
+    Revoke,
+    TransferOut,
+    TransferIn,
+    Adjustment,
+}
+
+impl MovementKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MovementKind::Initial => "initial",
+            MovementKind::Grant => "grant",
+            MovementKind::Revoke => "revoke",
+            MovementKind::TransferOut => "transfer_out",
+            MovementKind::TransferIn => "transfer_in",
+            MovementKind::Adjustment => "adjustment",
+        }
+    }
+}
+
+impl fmt::Display for MovementKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for MovementKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "initial" => Ok(MovementKind::Initial),
+            "grant" => Ok(MovementKind::Grant),
+            "revoke" => Ok(MovementKind::Revoke),
+            "transfer_out" => Ok(MovementKind::TransferOut),
+            "transfer_in" => Ok(MovementKind::TransferIn),
+            "adjustment" => Ok(MovementKind::Adjustment),
+            _ => Err(format!("unknown movement kind `{}`", value)),
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for MovementKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for MovementKind {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(value.parse::<MovementKind>()?)
+    }
 }
 
 #[derive(Queryable, Selectable, Identifiable, Associations, Clone)]
@@ -52,17 +144,50 @@ pub struct NewBalance {
 pub struct Movement {
     pub id: i32,
     pub person_id: i32,
-    pub value: f32,
+    pub value: i64,
     pub actor: String,
     pub date: NaiveDateTime,
+    pub kind: MovementKind,
+    pub counterparty_person_id: Option<i32>,
+    pub transfer_id: Option<String>,
+    pub memo: Option<String>,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = movement)]
 pub struct NewMovement {
     pub person_id: i32,
-    pub value: f32,
+    pub value: i64,
     pub actor: String,
+    pub kind: MovementKind,
+    pub counterparty_person_id: Option<i32>,
+    pub transfer_id: Option<String>,
+    pub memo: Option<String>,
+}
+
+impl NewMovement {
+    pub fn new(person: &Person, value: i64, actor: &str, kind: MovementKind) -> Self {
+        NewMovement {
+            person_id: person.id,
+            value,
+            actor: actor.to_string(),
+            kind,
+            counterparty_person_id: None,
+            transfer_id: None,
+            memo: None,
+        }
+    }
+
+    pub fn with_memo(mut self, memo: &Option<String>) -> Self {
+        self.memo = memo.clone();
+        self
+    }
+
+    pub fn with_counterparty(mut self, counterparty: &Person, transfer_id: &str) -> Self {
+        self.counterparty_person_id = Some(counterparty.id);
+        self.transfer_id = Some(transfer_id.to_string());
+        self
+    }
 }
 
 #[derive(Queryable, Selectable, Identifiable, Associations, Clone)]
@@ -72,8 +197,16 @@ pub struct User {
     pub id: i32,
     pub password: String,
     pub person_id: i32,
-    pub superuser: bool,
     pub username: String,
+    pub must_change_password: bool,
+    pub role: String,
+    pub failed_logins: i32,
+    pub locked_until: Option<NaiveDateTime>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub service: bool,
+    pub totp_last_step: Option<i64>,
+    pub totp_pending_secret: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -81,6 +214,117 @@ pub struct User {
 pub struct NewUser {
     pub password: String,
     pub person_id: i32,
-    pub superuser: bool,
     pub username: String,
+    pub must_change_password: bool,
+    pub role: String,
+    pub service: bool,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Clone, Debug)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = api_key)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_id: i32,
+    pub key_hash: String,
+    pub scopes: String,
+    pub max_points: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = api_key)]
+pub struct NewApiKey {
+    pub id: String,
+    pub user_id: i32,
+    pub key_hash: String,
+    pub scopes: String,
+    pub max_points: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Clone, Debug)]
+#[diesel(table_name = role, primary_key(name))]
+pub struct Role {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = recovery_code)]
+pub struct NewRecoveryCode {
+    pub user_id: i32,
+    pub code_hash: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Clone, Debug)]
+#[diesel(table_name = login_attempt)]
+pub struct LoginAttempt {
+    pub id: i32,
+    pub username: String,
+    pub attempted_at: NaiveDateTime,
+    pub success: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = login_attempt)]
+pub struct NewLoginAttempt {
+    pub username: String,
+    pub attempted_at: NaiveDateTime,
+    pub success: bool,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Clone, Debug)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = session)]
+pub struct Session {
+    pub id: String,
+    pub user_id: i32,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = session)]
+pub struct NewSession {
+    pub id: String,
+    pub user_id: i32,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Clone, Debug)]
+#[diesel(table_name = exchange_rate)]
+pub struct ExchangeRate {
+    pub id: i32,
+    pub currency: String,
+    pub rate: String,
+    pub fetched_at: NaiveDateTime,
+    pub source: String,
+    pub base: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = exchange_rate)]
+pub struct NewExchangeRate {
+    pub currency: String,
+    pub rate: String,
+    pub fetched_at: NaiveDateTime,
+    pub source: String,
+    pub base: String,
+}
+
+/// The columns of a `user` row that every schema since usernames were added has, read
+/// with plain SQL so that it works while migrations are pending.
+#[derive(QueryableByName)]
+pub struct SchemaAdmin {
+    #[diesel(sql_type = Text)]
+    pub password: String,
+    #[diesel(sql_type = Bool)]
+    pub granted: bool,
 }
@@ -1,10 +1,44 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_key (id) {
+        id -> Text,
+        user_id -> Integer,
+        key_hash -> Text,
+        scopes -> Text,
+        max_points -> Nullable<BigInt>,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     balance (id) {
         id -> Integer,
         person_id -> Integer,
-        value -> Float,
+        value -> BigInt,
+    }
+}
+
+diesel::table! {
+    exchange_rate (id) {
+        id -> Integer,
+        currency -> Text,
+        rate -> Text,
+        fetched_at -> Timestamp,
+        source -> Text,
+        base -> Text,
+    }
+}
+
+diesel::table! {
+    login_attempt (id) {
+        id -> Integer,
+        username -> Text,
+        attempted_at -> Timestamp,
+        success -> Bool,
     }
 }
 
@@ -12,9 +46,13 @@ diesel::table! {
     movement (id) {
         id -> Integer,
         person_id -> Integer,
-        value -> Float,
+        value -> BigInt,
         actor -> Text,
         date -> Timestamp,
+        kind -> Text,
+        counterparty_person_id -> Nullable<Integer>,
+        transfer_id -> Nullable<Text>,
+        memo -> Nullable<Text>,
     }
 }
 
@@ -26,6 +64,42 @@ diesel::table! {
         role -> Text,
         currency -> Text,
         dept -> Text,
+        active -> Bool,
+        terminated_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    permission (id) {
+        id -> Integer,
+        role -> Text,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    recovery_code (id) {
+        id -> Integer,
+        user_id -> Integer,
+        code_hash -> Text,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    role (name) {
+        name -> Text,
+        description -> Text,
+    }
+}
+
+diesel::table! {
+    session (id) {
+        id -> Text,
+        user_id -> Integer,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
     }
 }
 
@@ -34,13 +108,37 @@ diesel::table! {
         id -> Integer,
         password -> Text,
         person_id -> Integer,
-        superuser -> Bool,
         username -> Text,
+        must_change_password -> Bool,
+        role -> Text,
+        failed_logins -> Integer,
+        locked_until -> Nullable<Timestamp>,
+        totp_secret -> Nullable<Text>,
+        totp_enabled -> Bool,
+        service -> Bool,
+        totp_last_step -> Nullable<BigInt>,
+        totp_pending_secret -> Nullable<Text>,
     }
 }
 
+diesel::joinable!(api_key -> user (user_id));
 diesel::joinable!(balance -> person (person_id));
 diesel::joinable!(movement -> person (person_id));
+diesel::joinable!(permission -> role (role));
+diesel::joinable!(recovery_code -> user (user_id));
+diesel::joinable!(session -> user (user_id));
 diesel::joinable!(user -> person (person_id));
 
-diesel::allow_tables_to_appear_in_same_query!(balance, movement, person, user,);
+diesel::allow_tables_to_appear_in_same_query!(
+    api_key,
+    balance,
+    exchange_rate,
+    login_attempt,
+    movement,
+    permission,
+    person,
+    recovery_code,
+    role,
+    session,
+    user,
+);
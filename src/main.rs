@@ -1,19 +1,23 @@
 pub mod auth;
 pub mod cli;
+pub mod config;
 pub mod core;
 pub mod database;
 pub mod security;
 pub mod serializers;
 pub mod utils;
 
+use std::process::ExitCode;
+
 use clap::Parser;
 
-fn main() {
+fn main() -> ExitCode {
     let cli = cli::Cli::parse();
     match cli::match_command(&cli) {
-        Ok(_) => (),
+        Ok(_) => ExitCode::SUCCESS,
         Err(error) => {
-            println!("{:?}", error)
+            eprintln!("Error: {}", error);
+            ExitCode::from(error.exit_status() as u8)
         }
     }
 }
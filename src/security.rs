@@ -1,41 +1,231 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::password_hash::{
+    Error as PasswordHashError, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use hmac::{Hmac, Mac};
 use passwords::hasher;
-use std::fs;
+use sha2::{Digest, Sha256};
 
-pub fn generate_salt() -> [u8; 16] {
-    let salt = hasher::gen_salt();
-    save_salt(salt);
-    salt
+const HASH_PREFIX: &str = "$argon2";
+
+/// Generates a fresh random salt for a single credential. The salt is stored inside the
+/// resulting PHC string, so nothing has to be persisted next to the binary.
+pub fn generate_salt() -> Result<SaltString, PasswordHashError> {
+    SaltString::encode_b64(&hasher::gen_salt())
 }
 
-pub fn get_salt() -> [u8; 16] {
-    load_salt()
+pub fn hash_password(password: &str) -> Result<String, PasswordHashError> {
+    let salt = generate_salt()?;
+
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
 }
 
-fn save_salt(salt: [u8; 16]) {
-    let string_salt = salt.map(|x| x.to_string()).join("");
-    fs::write(".secrets.toml", format!("salt = \"{}\"", string_salt)).unwrap();
+/// Verifies a password against the value stored in the `user` table.
+///
+/// Stored values are PHC strings (`$argon2id$v=19$m=...,t=...,p=...$salt$hash`).
+/// Rows written before hashing was introduced still hold the cleartext password,
+/// those are compared directly so the user can log in once and get rehashed. A value
+/// that looks like a PHC string but cannot be parsed is reported as an error.
+pub fn verify_password(
+    plain_password: &str,
+    hashed_password: &str,
+) -> Result<bool, PasswordHashError> {
+    if !hashed_password.starts_with(HASH_PREFIX) {
+        return Ok(plain_password == hashed_password);
+    }
+
+    let parsed_hash = PasswordHash::new(hashed_password)?;
+
+    match Argon2::default().verify_password(plain_password.as_bytes(), &parsed_hash) {
+        Ok(()) => Ok(true),
+        Err(PasswordHashError::Password) => Ok(false),
+        Err(error) => Err(error),
+    }
 }
 
-fn load_salt() -> [u8; 16] {
-    let content = fs::read_to_string(".secrets.toml").unwrap();
-    let string_salt = content.split(" = ").collect::<Vec<&str>>()[1];
-    let u8_salt = string_salt
-        .chars()
-        .map(|x| x.to_digit(10).unwrap())
-        .collect::<Vec<u32>>()
-        .into_iter()
-        .map(|x| x as u8)
-        .collect::<Vec<u8>>();
+/// Whether the stored value is cleartext or was hashed with parameters other than
+/// the current ones, in which case it should be replaced on the next successful login.
+pub fn needs_rehash(hashed_password: &str) -> bool {
+    match PasswordHash::new(hashed_password) {
+        Ok(parsed_hash) => {
+            let current = Params::default();
 
-    let mut arr = [0u8; 16];
+            parsed_hash.algorithm != Algorithm::default().ident()
+                || parsed_hash.version != Some(Version::default().into())
+                || match Params::try_from(&parsed_hash) {
+                    Ok(params) => {
+                        params.m_cost() != current.m_cost()
+                            || params.t_cost() != current.t_cost()
+                            || params.p_cost() != current.p_cost()
+                    }
+                    Err(_) => true,
+                }
+        }
+        Err(_) => true,
+    }
+}
+
+const SIGNING_KEY_INFO: &[u8] = b"dundie-rewards session signing";
+const ENCRYPTION_KEY_INFO: &[u8] = b"dundie-rewards totp encryption";
+
+/// HKDF-SHA256 (RFC 5869) of `secret` with an empty salt, so that every purpose the
+/// secret key is used for gets its own, unrelated 32 byte key.
+fn derive_key(secret: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut extract = <Hmac<Sha256> as Mac>::new_from_slice(&[]).expect("any key length");
+    extract.update(secret);
+    let pseudo_random_key = extract.finalize().into_bytes();
 
-    for (place, element) in arr.iter_mut().zip(u8_salt.iter()) {
-        *place = *element;
+    let mut expand =
+        <Hmac<Sha256> as Mac>::new_from_slice(&pseudo_random_key).expect("any key length");
+    expand.update(info);
+    expand.update(&[1]);
+    expand.finalize().into_bytes().into()
+}
+
+fn signing_mac(secret: &str) -> Hmac<Sha256> {
+    let key = derive_key(secret.as_bytes(), SIGNING_KEY_INFO);
+    <Hmac<Sha256> as Mac>::new_from_slice(&key).expect("any key length")
+}
+
+/// HMAC-SHA256 of `payload` under a key derived from `secret`, hex encoded.
+pub fn sign(secret: &str, payload: &str) -> String {
+    let mut mac = signing_mac(secret);
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Checks a signature made by [`sign`] in constant time.
+pub fn verify_signature(secret: &str, payload: &str, signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = signing_mac(secret);
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn cipher(secret: &str) -> Aes256Gcm {
+    let key = derive_key(secret.as_bytes(), ENCRYPTION_KEY_INFO);
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+/// Encrypts `plaintext` with AES-256-GCM under a key derived from `secret`. The result
+/// is the hex encoded nonce followed by the ciphertext.
+pub fn encrypt(secret: &str, plaintext: &[u8]) -> String {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(secret)
+        .encrypt(&nonce, plaintext)
+        .expect("encryption does not fail for in-memory buffers");
+
+    hex::encode([nonce.as_slice(), &ciphertext].concat())
+}
+
+/// Reverses [`encrypt`]. Returns `None` when the key is wrong or the data was altered.
+pub fn decrypt(secret: &str, encrypted: &str) -> Option<Vec<u8>> {
+    let data = hex::decode(encrypted).ok()?;
+    if data.len() < 12 {
+        return None;
     }
+    let (nonce, ciphertext) = data.split_at(12);
+
+    cipher(secret)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .ok()
+}
 
-    arr
+/// SHA-256 of a random, high entropy token, hex encoded. Unlike passwords such tokens
+/// do not need a slow hash.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
 }
 
-pub fn verify_password(plain_password: &String, hashed_password: &String) -> bool {
-    return plain_password == hashed_password;
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::security::{
+        decrypt, derive_key, encrypt, hash_password, needs_rehash, sign, verify_password,
+        verify_signature, ENCRYPTION_KEY_INFO, SIGNING_KEY_INFO,
+    };
+
+    #[rstest]
+    fn positive_verify_hashed_password() {
+        let hashed = hash_password("s3cret pass").unwrap();
+
+        assert!(hashed.starts_with("$argon2id$v=19$"));
+        assert!(verify_password("s3cret pass", &hashed).unwrap());
+        assert!(!needs_rehash(&hashed));
+    }
+
+    #[rstest]
+    fn negative_verify_hashed_password() {
+        let hashed = hash_password("s3cret pass").unwrap();
+
+        assert!(!verify_password("wrong pass", &hashed).unwrap());
+    }
+
+    #[rstest]
+    fn legacy_plaintext_password() {
+        assert!(verify_password("admin", "admin").unwrap());
+        assert!(!verify_password("admin", "other").unwrap());
+        assert!(needs_rehash("admin"));
+    }
+
+    #[rstest]
+    fn malformed_hashed_password() {
+        assert!(verify_password("admin", "$argon2id$v=19$m=abc,t=2,p=1$c2FsdA$aGFzaA").is_err());
+    }
+
+    #[rstest]
+    fn different_salt_per_hash() {
+        let first = hash_password("admin").unwrap();
+        let second = hash_password("admin").unwrap();
+
+        assert_ne!(first, second);
+        assert!(verify_password("admin", &first).unwrap());
+        assert!(verify_password("admin", &second).unwrap());
+    }
+
+    #[rstest]
+    fn signature_round_trip() {
+        let signature = sign("secret", "payload");
+
+        assert!(verify_signature("secret", "payload", &signature));
+        assert!(!verify_signature("other", "payload", &signature));
+        assert!(!verify_signature("secret", "tampered", &signature));
+        assert!(!verify_signature("secret", "payload", "not hex"));
+    }
+
+    #[rstest]
+    fn encryption_round_trip() {
+        let encrypted = encrypt("secret", b"totp seed");
+
+        assert_ne!(encrypt("secret", b"totp seed"), encrypted);
+        assert_eq!(decrypt("secret", &encrypted).unwrap(), b"totp seed");
+        assert!(decrypt("other", &encrypted).is_none());
+        assert!(decrypt("secret", "00").is_none());
+    }
+
+    #[rstest]
+    fn derived_key_matches_rfc_5869() {
+        // Test case 3 of RFC 5869, which uses an empty salt and info.
+        let key = derive_key(&[0x0b; 22], &[]);
+
+        assert_eq!(
+            hex::encode(key),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d"
+        );
+    }
+
+    #[rstest]
+    fn keys_differ_per_purpose() {
+        assert_ne!(
+            derive_key(b"secret", SIGNING_KEY_INFO),
+            derive_key(b"secret", ENCRYPTION_KEY_INFO)
+        );
+    }
 }
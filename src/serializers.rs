@@ -1,6 +1,11 @@
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-use crate::database::models::NewPerson;
+use crate::database::models::{Movement, MovementKind, NewPerson};
+use crate::utils::output::Record;
+use crate::utils::points::{format_points, serialize_points};
 
 #[derive(Debug, Deserialize)]
 pub struct PersonIn {
@@ -11,6 +16,7 @@ pub struct PersonIn {
     pub currency: String,
 }
 
+#[allow(clippy::from_over_into)]
 impl Into<NewPerson> for PersonIn {
     fn into(self) -> NewPerson {
         NewPerson {
@@ -23,7 +29,7 @@ impl Into<NewPerson> for PersonIn {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 pub struct PersonOut {
     pub name: String,
     pub dept: String,
@@ -31,24 +37,27 @@ pub struct PersonOut {
     pub email: String,
     pub currency: String,
     pub created: bool,
-    pub balance: f32,
-    pub value: f32,
+    #[serde(serialize_with = "serialize_points")]
+    pub balance: i64,
+    pub value: Option<Decimal>,
 }
 
-impl PersonOut {
-    pub fn fields(&self) -> Vec<String> {
+impl Record for PersonOut {
+    fn fields() -> Vec<&'static str> {
         vec![
-            "name".to_string(),
-            "dept".to_string(),
-            "role".to_string(),
-            "email".to_string(),
-            "currency".to_string(),
-            "created".to_string(),
-            "balance".to_string(),
-            "value".to_string(),
+            "name", "dept", "role", "email", "currency", "created", "balance", "value",
         ]
     }
 
+    fn row(&self) -> Vec<String> {
+        Self::fields()
+            .into_iter()
+            .map(|field| self.get(field))
+            .collect()
+    }
+}
+
+impl PersonOut {
     pub fn get(&self, key: &str) -> String {
         match key {
             "name" => self.name.clone(),
@@ -57,10 +66,282 @@ impl PersonOut {
             "email" => self.email.clone(),
             "currency" => self.currency.clone(),
             "created" => self.created.to_string(),
-            "balance" => self.balance.to_string(),
-            "value" => self.value.to_string(),
+            "balance" => format_points(self.balance),
+            "value" => self
+                .value
+                .map(|value| value.to_string())
+                .unwrap_or_default(),
             //TODO: create an error
             _ => "".to_string(),
         }
     }
 }
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MovementOut {
+    pub person: String,
+    pub date: NaiveDateTime,
+    pub kind: MovementKind,
+    #[serde(serialize_with = "serialize_points")]
+    pub value: i64,
+    #[serde(serialize_with = "serialize_points")]
+    pub balance: i64,
+    pub counterparty: Option<String>,
+    pub transfer_id: Option<String>,
+    pub memo: Option<String>,
+    pub actor: String,
+}
+
+impl Record for MovementOut {
+    fn fields() -> Vec<&'static str> {
+        vec![
+            "person",
+            "date",
+            "kind",
+            "value",
+            "balance",
+            "counterparty",
+            "transfer_id",
+            "memo",
+            "actor",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.person.clone(),
+            self.date.to_string(),
+            self.kind.to_string(),
+            format_points(self.value),
+            format_points(self.balance),
+            self.counterparty.clone().unwrap_or_default(),
+            self.transfer_id.clone().unwrap_or_default(),
+            self.memo.clone().unwrap_or_default(),
+            self.actor.clone(),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct StatementSummary {
+    #[serde(serialize_with = "serialize_points")]
+    pub opening_balance: i64,
+    #[serde(serialize_with = "serialize_points")]
+    pub closing_balance: i64,
+    pub movements: usize,
+}
+
+impl Record for StatementSummary {
+    fn fields() -> Vec<&'static str> {
+        vec!["opening_balance", "closing_balance", "movements"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            format_points(self.opening_balance),
+            format_points(self.closing_balance),
+            self.movements.to_string(),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Sign {
+    Credit,
+    Debit,
+}
+
+/// Which movements a statement shows. `since` and `until` set the period, the other
+/// filters only hide rows and never change the balances.
+#[derive(Clone, Debug, Default)]
+pub struct MovementFilter {
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    pub actor: Option<String>,
+    pub sign: Option<Sign>,
+    pub kind: Option<MovementKind>,
+    pub order: SortOrder,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl MovementFilter {
+    pub fn matches(&self, movement: &Movement) -> bool {
+        let actor_matches = match &self.actor {
+            Some(actor) => &movement.actor == actor,
+            None => true,
+        };
+        let sign_matches = match self.sign {
+            Some(Sign::Credit) => movement.value > 0,
+            Some(Sign::Debit) => movement.value < 0,
+            None => true,
+        };
+        let kind_matches = match self.kind {
+            Some(kind) => movement.kind == kind,
+            None => true,
+        };
+
+        actor_matches && sign_matches && kind_matches
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SessionOut {
+    pub id: String,
+    pub username: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl Record for SessionOut {
+    fn fields() -> Vec<&'static str> {
+        vec!["id", "username", "created_at", "expires_at", "revoked_at"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.username.clone(),
+            self.created_at.to_string(),
+            self.expires_at.to_string(),
+            self.revoked_at
+                .map(|revoked_at| revoked_at.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ApiKeyOut {
+    pub id: String,
+    pub username: String,
+    pub scopes: String,
+    pub max_points: Option<Decimal>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl Record for ApiKeyOut {
+    fn fields() -> Vec<&'static str> {
+        vec![
+            "id",
+            "username",
+            "scopes",
+            "max_points",
+            "created_at",
+            "expires_at",
+            "last_used_at",
+            "revoked_at",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.username.clone(),
+            self.scopes.clone(),
+            self.max_points
+                .map(|max_points| max_points.to_string())
+                .unwrap_or_default(),
+            self.created_at.to_string(),
+            self.expires_at.to_string(),
+            self.last_used_at
+                .map(|last_used_at| last_used_at.to_string())
+                .unwrap_or_default(),
+            self.revoked_at
+                .map(|revoked_at| revoked_at.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MigrationOut {
+    pub name: String,
+    pub applied: bool,
+}
+
+impl Record for MigrationOut {
+    fn fields() -> Vec<&'static str> {
+        vec!["name", "applied"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![self.name.clone(), self.applied.to_string()]
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RowError {
+    pub line: u64,
+    pub field: String,
+    pub value: String,
+    pub message: String,
+}
+
+impl Record for RowError {
+    fn fields() -> Vec<&'static str> {
+        vec!["line", "field", "value", "message"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.line.to_string(),
+            self.field.clone(),
+            self.value.clone(),
+            self.message.clone(),
+        ]
+    }
+}
+
+impl RowError {
+    pub fn new(line: u64, field: &str, value: &str, message: &str) -> Self {
+        RowError {
+            line,
+            field: field.to_string(),
+            value: value.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PersonChange {
+    pub email: String,
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl Record for PersonChange {
+    fn fields() -> Vec<&'static str> {
+        vec!["email", "field", "old", "new"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.email.clone(),
+            self.field.clone(),
+            self.old.clone(),
+            self.new.clone(),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct LoadReport {
+    pub people: Vec<PersonOut>,
+    pub changes: Vec<PersonChange>,
+    pub missing: Vec<PersonOut>,
+    pub row_errors: Vec<RowError>,
+}
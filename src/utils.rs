@@ -1,5 +1,10 @@
 pub mod cli;
+pub mod currency;
+pub mod date;
 pub mod db;
 pub mod email;
 pub mod exchange;
+pub mod import;
+pub mod output;
+pub mod points;
 pub mod user;
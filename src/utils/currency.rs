@@ -0,0 +1,137 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// Used for amounts in a currency missing from the table.
+const DEFAULT_MINOR_UNITS: u32 = 2;
+
+/// Number of minor units (digits after the decimal separator) of ISO 4217 codes, used when
+/// displaying amounts. Every currency with other than two minor units is listed.
+const CURRENCIES: [(&str, u32); 57] = [
+    ("ARS", 2),
+    ("AUD", 2),
+    ("BHD", 3),
+    ("BIF", 0),
+    ("BRL", 2),
+    ("CAD", 2),
+    ("CHF", 2),
+    ("CLP", 0),
+    ("CNY", 2),
+    ("COP", 2),
+    ("CZK", 2),
+    ("DJF", 0),
+    ("DKK", 2),
+    ("EUR", 2),
+    ("GBP", 2),
+    ("GNF", 0),
+    ("HKD", 2),
+    ("HUF", 2),
+    ("IDR", 2),
+    ("ILS", 2),
+    ("INR", 2),
+    ("IQD", 3),
+    ("ISK", 0),
+    ("JOD", 3),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("MXN", 2),
+    ("MYR", 2),
+    ("NOK", 2),
+    ("NZD", 2),
+    ("OMR", 3),
+    ("PEN", 2),
+    ("PHP", 2),
+    ("PLN", 2),
+    ("PYG", 0),
+    ("RON", 2),
+    ("RUB", 2),
+    ("RWF", 0),
+    ("SAR", 2),
+    ("SEK", 2),
+    ("SGD", 2),
+    ("THB", 2),
+    ("TND", 3),
+    ("TRY", 2),
+    ("TWD", 2),
+    ("UGX", 0),
+    ("USD", 2),
+    ("UYU", 2),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+    ("XPF", 0),
+    ("ZAR", 2),
+];
+
+/// Checks that a code has the shape of an ISO 4217 code: three uppercase ASCII letters.
+/// Whether the code exists is left to the exchange provider, which reports codes it lacks.
+pub fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.bytes().all(|byte| byte.is_ascii_uppercase())
+}
+
+pub fn minor_units(code: &str) -> Option<u32> {
+    CURRENCIES
+        .iter()
+        .find(|(known_code, _)| *known_code == code)
+        .map(|(_, units)| *units)
+}
+
+/// Rounds an amount to the minor units of its currency, half away from zero, and pads it
+/// so that it always displays that many decimals.
+pub fn round_to_minor_units(value: Decimal, code: &str) -> Decimal {
+    let units = minor_units(code).unwrap_or(DEFAULT_MINOR_UNITS);
+    let mut rounded = value.round_dp_with_strategy(units, RoundingStrategy::MidpointAwayFromZero);
+    rounded.rescale(units);
+    rounded
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use crate::utils::currency::{is_currency_code, minor_units, round_to_minor_units};
+
+    #[rstest]
+    #[case("USD", 2)]
+    #[case("BRL", 2)]
+    #[case("JPY", 0)]
+    #[case("KWD", 3)]
+    #[case("XOF", 0)]
+    fn known_currency_minor_units(#[case] code: &str, #[case] expected: u32) {
+        assert_eq!(minor_units(code), Some(expected));
+    }
+
+    #[rstest]
+    #[case("USD", true)]
+    #[case("XXX", true)]
+    #[case("MNT", true)]
+    #[case("usd", false)]
+    #[case("US", false)]
+    #[case("USDX", false)]
+    #[case("U$D", false)]
+    #[case("", false)]
+    fn currency_code_shape(#[case] code: &str, #[case] expected: bool) {
+        assert_eq!(is_currency_code(code), expected);
+    }
+
+    #[rstest]
+    #[case("92", "EUR", "92.00")]
+    #[case("2475.005", "BRL", "2475.01")]
+    #[case("15123.5", "JPY", "15124")]
+    #[case("1.23456", "KWD", "1.235")]
+    #[case("1.234", "XYZ", "1.23")]
+    fn round_amount_to_minor_units(
+        #[case] value: &str,
+        #[case] code: &str,
+        #[case] expected: &str,
+    ) {
+        let rounded = round_to_minor_units(Decimal::from_str(value).unwrap(), code);
+        assert_eq!(rounded.to_string(), expected);
+    }
+}
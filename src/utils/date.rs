@@ -0,0 +1,58 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DATE_TIME_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"];
+
+fn parse_period_bound(value: &str, end_of_day: bool) -> Result<NaiveDateTime, String> {
+    let trimmed = value.trim();
+
+    for format in DATE_TIME_FORMATS {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(trimmed, format) {
+            return Ok(date_time);
+        }
+    }
+
+    match NaiveDate::parse_from_str(trimmed, DATE_FORMAT) {
+        Ok(date) if end_of_day => Ok(date.and_hms_nano_opt(23, 59, 59, 999_999_999).unwrap()),
+        Ok(date) => Ok(date.and_time(NaiveTime::MIN)),
+        Err(_) => Err(format!(
+            "`{}` is not a date, expected YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS",
+            value
+        )),
+    }
+}
+
+/// Start of a period: a plain date means the start of that day.
+pub fn parse_since(value: &str) -> Result<NaiveDateTime, String> {
+    parse_period_bound(value, false)
+}
+
+/// End of a period, inclusive: a plain date means the whole of that day.
+pub fn parse_until(value: &str) -> Result<NaiveDateTime, String> {
+    parse_period_bound(value, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::utils::date::{parse_since, parse_until};
+
+    #[rstest]
+    #[case("2023-05-01", "2023-05-01 00:00:00", "2023-05-01 23:59:59.999999999")]
+    #[case("2023-05-01T12:30:00", "2023-05-01 12:30:00", "2023-05-01 12:30:00")]
+    #[case("2023-05-01 12:30:00", "2023-05-01 12:30:00", "2023-05-01 12:30:00")]
+    fn positive_parse_period(#[case] input: &str, #[case] since: &str, #[case] until: &str) {
+        assert_eq!(parse_since(input).unwrap().to_string(), since);
+        assert_eq!(parse_until(input).unwrap().to_string(), until);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("yesterday")]
+    #[case("2023-13-01")]
+    fn negative_parse_period(#[case] input: &str) {
+        assert!(parse_since(input).is_err());
+        assert!(parse_until(input).is_err());
+    }
+}
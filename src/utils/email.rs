@@ -2,6 +2,7 @@ use regex::Regex;
 
 const REGEX: &str = r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b";
 
+#[allow(clippy::ptr_arg)]
 pub fn email_validator(address: &String) -> Result<(), serde_valid::validation::Error> {
     let re = Regex::new(REGEX).unwrap();
     if re.is_match(address) {
@@ -14,6 +15,7 @@ pub fn email_validator(address: &String) -> Result<(), serde_valid::validation::
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test {
     use rstest::rstest;
 
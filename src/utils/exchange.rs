@@ -1,79 +1,177 @@
+pub mod file;
+pub mod fixed;
+pub mod http;
+
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Error as IoError;
+use std::str::FromStr;
 
 use reqwest::Error as ReqwestError;
-use serde::{Deserialize, Serialize};
-use serde_json::{Error as SerdeError, Value};
+use rust_decimal::Decimal;
+use serde_json::Error as SerdeError;
+use toml::de::Error as TomlError;
 
-const API_BASE_URL: &str = "https://economia.awesomeapi.com.br/json/last/USD-";
+use crate::config;
+use crate::utils::exchange::file::FileProvider;
+use crate::utils::exchange::fixed::FixedProvider;
+use crate::utils::exchange::http::{HttpProvider, QuoteField};
 
 #[derive(Debug)]
 pub enum ExchangeError {
     Reqwest(ReqwestError),
     Serde(SerdeError),
-    NotFound,
+    Toml(TomlError),
+    Io(IoError),
+    Config(String),
+    Parse(String),
+    NotFound(String),
+}
+
+impl fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExchangeError::Reqwest(error) => {
+                write!(f, "could not reach the exchange rate service: {}", error)
+            }
+            ExchangeError::Serde(error) => write!(f, "unexpected exchange rate data: {}", error),
+            ExchangeError::Toml(error) => write!(f, "invalid exchange rates file: {}", error),
+            ExchangeError::Io(error) => write!(f, "could not read the exchange rates: {}", error),
+            ExchangeError::Config(message) => write!(f, "{}", message),
+            ExchangeError::Parse(message) => write!(f, "{}", message),
+            ExchangeError::NotFound(currency) => write!(f, "no exchange rate for {}", currency),
+        }
+    }
+}
+
+impl Error for ExchangeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExchangeError::Reqwest(error) => Some(error),
+            ExchangeError::Serde(error) => Some(error),
+            ExchangeError::Toml(error) => Some(error),
+            ExchangeError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ReqwestError> for ExchangeError {
+    fn from(value: ReqwestError) -> Self {
+        Self::Reqwest(value)
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct USDRate {
-    #[serde(default = "default_code")]
-    pub code: String,
-    #[serde(default = "default_code")]
-    pub codein: String,
-    #[serde(default = "default_name")]
-    pub name: String,
-    #[serde(alias = "high")]
-    pub value: String,
+impl From<SerdeError> for ExchangeError {
+    fn from(value: SerdeError) -> Self {
+        Self::Serde(value)
+    }
 }
 
-fn default_code() -> String {
-    "USD".to_string()
+impl From<TomlError> for ExchangeError {
+    fn from(value: TomlError) -> Self {
+        Self::Toml(value)
+    }
 }
 
-fn default_name() -> String {
-    "Dolar/Dolar".to_string()
+impl From<IoError> for ExchangeError {
+    fn from(value: IoError) -> Self {
+        Self::Io(value)
+    }
 }
 
-pub fn get_rates(currencies: Vec<String>) -> Result<HashMap<String, USDRate>, ExchangeError> {
-    let mut result: HashMap<String, USDRate> = HashMap::new();
-    let mut rate;
-
-    for currency in currencies {
-        if currency == default_code() {
-            match serde_json::from_str::<USDRate>("{\"high\": \"1\" }") {
-                Ok(converted) => rate = converted,
-                Err(error) => return Err(ExchangeError::Serde(error)),
-            };
-        } else {
-            let response = fetch_api(&currency)?;
-            rate = convert_response(response, &currency)?;
+/// How many units of `currency` one unit of `base` buys.
+#[derive(Clone, Debug)]
+pub struct Rate {
+    pub base: String,
+    pub currency: String,
+    pub value: Decimal,
+}
+
+impl Rate {
+    pub fn new(base: &str, currency: &str, value: Decimal) -> Self {
+        Rate {
+            base: base.to_string(),
+            currency: currency.to_string(),
+            value,
         }
-        result.insert(currency, rate);
     }
+}
 
-    Ok(result)
+/// Parses a rate as sent by a provider or stored in the cache. Rates must be positive.
+pub fn parse_rate(currency: &str, value: &str) -> Result<Decimal, ExchangeError> {
+    match Decimal::from_str(value.trim()) {
+        Ok(rate) if rate > Decimal::ZERO => Ok(rate),
+        Ok(_) => Err(ExchangeError::Parse(format!(
+            "the {} rate must be positive, got '{}'",
+            currency, value
+        ))),
+        Err(error) => Err(ExchangeError::Parse(format!(
+            "invalid {} rate '{}': {}",
+            currency, value, error
+        ))),
+    }
 }
 
-fn fetch_api(currency: &String) -> Result<String, ExchangeError> {
-    match reqwest::blocking::get(format!("{}{}", API_BASE_URL, currency)) {
-        Ok(response_object) => match response_object.text() {
-            Ok(response) => Ok(response),
-            Err(error) => Err(ExchangeError::Reqwest(error)),
-        },
-        Err(error) => Err(ExchangeError::Reqwest(error)),
+/// The outcome of a lookup for each requested currency.
+pub type RateResults = HashMap<String, Result<Rate, ExchangeError>>;
+
+/// A source of exchange rates from the points base currency.
+pub trait ExchangeProvider {
+    fn name(&self) -> &'static str;
+    fn get_rate(&self, base: &str, currency: &str) -> Result<Rate, ExchangeError>;
+
+    /// Looks up several currencies at once. A currency that fails does not stop the
+    /// others; only a failure of the whole lookup is returned as an error.
+    fn get_rates(&self, base: &str, currencies: &[String]) -> Result<RateResults, ExchangeError> {
+        Ok(currencies
+            .iter()
+            .map(|currency| (currency.clone(), self.get_rate(base, currency)))
+            .collect())
     }
 }
 
-fn convert_response(response: String, currency: &String) -> Result<USDRate, ExchangeError> {
-    match serde_json::from_str::<Value>(&response) {
-        Ok(mut converted_value) => match converted_value.get_mut(format!("USD{}", currency)) {
-            Some(value_response) => {
-                match serde_json::from_str::<USDRate>(&value_response.take().to_string()) {
-                    Ok(result) => Ok(result),
-                    Err(error) => Err(ExchangeError::Serde(error)),
-                }
-            }
-            None => Err(ExchangeError::NotFound),
-        },
-        Err(error) => Err(ExchangeError::Serde(error)),
+/// Builds the provider selected by `DUNDIE_EXCHANGE_PROVIDER` (`http`, `file` or `fixed`).
+pub fn configured_provider() -> Result<Box<dyn ExchangeProvider>, ExchangeError> {
+    let settings = config::exchange_settings();
+
+    match settings.provider.as_str() {
+        "http" => Ok(Box::new(HttpProvider::new(
+            &settings.url,
+            QuoteField::from_str(&settings.quote)?,
+        ))),
+        "file" => Ok(Box::new(FileProvider::open(&settings.file)?)),
+        "fixed" => Ok(Box::new(FixedProvider::parse(&settings.rates)?)),
+        other => Err(ExchangeError::Config(format!(
+            "unknown exchange provider '{}'",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::utils::exchange::{parse_rate, ExchangeError};
+
+    #[rstest]
+    #[case("0.92", "0.92")]
+    #[case(" 4.9512 ", "4.9512")]
+    fn positive_parse_rate(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(parse_rate("EUR", input).unwrap().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("abc")]
+    #[case("0")]
+    #[case("-1.5")]
+    fn negative_parse_rate(#[case] input: &str) {
+        assert!(matches!(
+            parse_rate("EUR", input),
+            Err(ExchangeError::Parse(_))
+        ));
     }
 }
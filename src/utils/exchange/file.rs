@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::utils::exchange::{parse_rate, ExchangeError, ExchangeProvider, Rate};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RateValue {
+    Number(f64),
+    Text(String),
+}
+
+/// Rates read from a static file, one `CURRENCY = rate` entry per currency. Files ending
+/// in `.json` are read as a JSON object, anything else as TOML.
+pub struct FileProvider {
+    rates: HashMap<String, Decimal>,
+}
+
+impl FileProvider {
+    pub fn open(path: &str) -> Result<Self, ExchangeError> {
+        let content = fs::read_to_string(path)?;
+        let is_json = Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+        FileProvider::parse(&content, is_json)
+    }
+
+    fn parse(content: &str, is_json: bool) -> Result<Self, ExchangeError> {
+        let values: HashMap<String, RateValue> = if is_json {
+            serde_json::from_str(content)?
+        } else {
+            toml::from_str(content)?
+        };
+
+        let mut rates: HashMap<String, Decimal> = HashMap::new();
+
+        for (currency, value) in values {
+            let currency = currency.trim().to_uppercase();
+            let value = match value {
+                RateValue::Number(number) => number.to_string(),
+                RateValue::Text(text) => text,
+            };
+            rates.insert(currency.clone(), parse_rate(&currency, &value)?);
+        }
+
+        Ok(FileProvider { rates })
+    }
+}
+
+impl ExchangeProvider for FileProvider {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn get_rate(&self, base: &str, currency: &str) -> Result<Rate, ExchangeError> {
+        match self.rates.get(currency) {
+            Some(value) => Ok(Rate::new(base, currency, *value)),
+            None => Err(ExchangeError::NotFound(currency.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::utils::exchange::file::FileProvider;
+    use crate::utils::exchange::ExchangeProvider;
+
+    #[rstest]
+    #[case("EUR = 0.92\nbrl = \"4.95\"\n", false)]
+    #[case(r#"{"EUR": 0.92, "brl": "4.95"}"#, true)]
+    fn parse_rates_file(#[case] content: &str, #[case] is_json: bool) {
+        let provider = FileProvider::parse(content, is_json).unwrap();
+
+        assert_eq!(
+            provider.get_rate("USD", "EUR").unwrap().value.to_string(),
+            "0.92"
+        );
+        assert_eq!(
+            provider.get_rate("USD", "BRL").unwrap().value.to_string(),
+            "4.95"
+        );
+        assert!(provider.get_rate("USD", "GBP").is_err());
+    }
+
+    #[rstest]
+    #[case("EUR = [")]
+    #[case("EUR = \"abc\"")]
+    fn invalid_rates_file(#[case] content: &str) {
+        assert!(FileProvider::parse(content, false).is_err());
+    }
+}
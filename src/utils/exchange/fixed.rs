@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use crate::utils::exchange::{parse_rate, ExchangeError, ExchangeProvider, Rate};
+
+/// Rates given inline, e.g. `EUR=0.92,BRL=4.95`. Meant for tests and for setups where
+/// the rates are pinned by hand.
+pub struct FixedProvider {
+    rates: HashMap<String, Decimal>,
+}
+
+impl FixedProvider {
+    pub fn parse(spec: &str) -> Result<Self, ExchangeError> {
+        let mut rates: HashMap<String, Decimal> = HashMap::new();
+
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((currency, value)) if !currency.trim().is_empty() => {
+                    let currency = currency.trim().to_uppercase();
+                    let rate = parse_rate(&currency, value)?;
+                    rates.insert(currency, rate);
+                }
+                _ => {
+                    return Err(ExchangeError::Config(format!(
+                        "invalid fixed rate '{}', expected CURRENCY=rate",
+                        entry
+                    )))
+                }
+            }
+        }
+
+        Ok(FixedProvider { rates })
+    }
+}
+
+impl ExchangeProvider for FixedProvider {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    fn get_rate(&self, base: &str, currency: &str) -> Result<Rate, ExchangeError> {
+        match self.rates.get(currency) {
+            Some(value) => Ok(Rate::new(base, currency, *value)),
+            None => Err(ExchangeError::NotFound(currency.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::utils::exchange::fixed::FixedProvider;
+    use crate::utils::exchange::ExchangeProvider;
+
+    #[rstest]
+    fn parse_fixed_rates() {
+        let provider = FixedProvider::parse("EUR=0.92, brl = 4.95").unwrap();
+
+        assert_eq!(
+            provider.get_rate("USD", "EUR").unwrap().value.to_string(),
+            "0.92"
+        );
+        assert_eq!(
+            provider.get_rate("USD", "BRL").unwrap().value.to_string(),
+            "4.95"
+        );
+    }
+
+    #[rstest]
+    #[case("EUR")]
+    #[case("=0.92")]
+    #[case("EUR=abc")]
+    fn invalid_fixed_rates(#[case] input: &str) {
+        assert!(FixedProvider::parse(input).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::utils::exchange::{parse_rate, ExchangeError, ExchangeProvider, Rate, RateResults};
+
+/// Which price of the awesomeapi quote is used as the rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuoteField {
+    Bid,
+    Ask,
+    High,
+}
+
+impl QuoteField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuoteField::Bid => "bid",
+            QuoteField::Ask => "ask",
+            QuoteField::High => "high",
+        }
+    }
+}
+
+impl FromStr for QuoteField {
+    type Err = ExchangeError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "bid" => Ok(QuoteField::Bid),
+            "ask" => Ok(QuoteField::Ask),
+            "high" => Ok(QuoteField::High),
+            other => Err(ExchangeError::Config(format!(
+                "unknown quote field '{}', expected bid, ask or high",
+                other
+            ))),
+        }
+    }
+}
+
+/// Rates from the awesomeapi `json/last` endpoint, which accepts several pairs in one
+/// request (`USD-EUR,USD-BRL`).
+pub struct HttpProvider {
+    base_url: String,
+    quote: QuoteField,
+}
+
+impl HttpProvider {
+    pub fn new(base_url: &str, quote: QuoteField) -> Self {
+        HttpProvider {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            quote,
+        }
+    }
+
+    fn fetch_api(&self, base: &str, currencies: &[String]) -> Result<String, ExchangeError> {
+        let pairs: Vec<String> = currencies
+            .iter()
+            .map(|currency| format!("{}-{}", base, currency))
+            .collect();
+        let url = format!("{}/{}", self.base_url, pairs.join(","));
+
+        Ok(reqwest::blocking::get(url)?.text()?)
+    }
+}
+
+impl ExchangeProvider for HttpProvider {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn get_rate(&self, base: &str, currency: &str) -> Result<Rate, ExchangeError> {
+        match self
+            .get_rates(base, &[currency.to_string()])?
+            .remove(currency)
+        {
+            Some(result) => result,
+            None => Err(ExchangeError::NotFound(currency.to_string())),
+        }
+    }
+
+    fn get_rates(&self, base: &str, currencies: &[String]) -> Result<RateResults, ExchangeError> {
+        if currencies.is_empty() {
+            return Ok(RateResults::new());
+        }
+
+        let response = self.fetch_api(base, currencies)?;
+        convert_response(response, base, currencies, self.quote)
+    }
+}
+
+/// Reads the `<base><currency>` entry of every currency from a response. Currencies
+/// missing from the response, or whose entry cannot be read, get their own error.
+fn convert_response(
+    response: String,
+    base: &str,
+    currencies: &[String],
+    quote: QuoteField,
+) -> Result<RateResults, ExchangeError> {
+    let converted_value = serde_json::from_str::<Value>(&response)?;
+
+    Ok(currencies
+        .iter()
+        .map(|currency| {
+            let result = match converted_value.get(format!("{}{}", base, currency)) {
+                Some(value_response) => {
+                    match value_response.get(quote.as_str()).and_then(Value::as_str) {
+                        Some(value) => {
+                            parse_rate(currency, value).map(|rate| Rate::new(base, currency, rate))
+                        }
+                        None => Err(ExchangeError::Parse(format!(
+                            "the {} quote has no '{}' field",
+                            currency,
+                            quote.as_str()
+                        ))),
+                    }
+                }
+                None => Err(ExchangeError::NotFound(currency.clone())),
+            };
+            (currency.clone(), result)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::rstest;
+
+    use crate::utils::exchange::http::{convert_response, QuoteField};
+    use crate::utils::exchange::ExchangeError;
+
+    const RESPONSE: &str = r#"{
+        "USDEUR": {"code": "USD", "codein": "EUR", "high": "0.9126", "bid": "0.9101", "ask": "0.9110"},
+        "USDBRL": {"code": "USD", "codein": "BRL", "high": "4.9512", "bid": "4.9400", "ask": "4.9500"}
+    }"#;
+
+    #[rstest]
+    #[case(QuoteField::High, "0.9126")]
+    #[case(QuoteField::Bid, "0.9101")]
+    #[case(QuoteField::Ask, "0.9110")]
+    fn positive_convert_response(#[case] quote: QuoteField, #[case] expected: &str) {
+        let currencies = vec!["EUR".to_string(), "BRL".to_string()];
+
+        let rates = convert_response(RESPONSE.to_string(), "USD", &currencies, quote).unwrap();
+
+        assert_eq!(rates["EUR"].as_ref().unwrap().value.to_string(), expected);
+        assert_eq!(rates["BRL"].as_ref().unwrap().currency, "BRL");
+    }
+
+    #[rstest]
+    fn partial_convert_response() {
+        let response = r#"{
+            "USDEUR": {"code": "USD", "codein": "EUR", "high": "0.9126"},
+            "USDGBP": {"code": "USD", "codein": "GBP", "high": "n/a"}
+        }"#;
+        let currencies = vec!["EUR".to_string(), "GBP".to_string(), "XYZ".to_string()];
+
+        let rates =
+            convert_response(response.to_string(), "USD", &currencies, QuoteField::High).unwrap();
+
+        assert!(rates["EUR"].is_ok());
+        assert!(matches!(rates["GBP"], Err(ExchangeError::Parse(_))));
+        assert!(matches!(rates["XYZ"], Err(ExchangeError::NotFound(_))));
+    }
+
+    #[rstest]
+    fn negative_convert_response() {
+        let currencies = vec!["EUR".to_string()];
+
+        let result = convert_response("not json".to_string(), "USD", &currencies, QuoteField::High);
+
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    fn parse_quote_field() {
+        assert_eq!(QuoteField::from_str("Bid").unwrap(), QuoteField::Bid);
+        assert!(QuoteField::from_str("close").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+
+use csv::{ReaderBuilder, StringRecord, Trim};
+
+use crate::serializers::{PersonIn, RowError};
+use crate::utils::currency::is_currency_code;
+use crate::utils::email::email_validator;
+
+/// Column order used when the file has no header row.
+const COLUMNS: [&str; 5] = ["name", "dept", "role", "email", "currency"];
+
+#[derive(Debug)]
+pub enum ImportError {
+    Csv(csv::Error),
+    MissingColumn(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Csv(error) => write!(f, "could not read the people file: {}", error),
+            ImportError::MissingColumn(column) => {
+                write!(f, "the people file has no '{}' column", column)
+            }
+        }
+    }
+}
+
+impl Error for ImportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImportError::Csv(error) => Some(error),
+            ImportError::MissingColumn(_) => None,
+        }
+    }
+}
+
+impl From<csv::Error> for ImportError {
+    fn from(value: csv::Error) -> Self {
+        Self::Csv(value)
+    }
+}
+
+/// Reads the people file, with or without a header row, and validates every row.
+///
+/// Valid rows are returned as `PersonIn`, invalid ones are collected as `RowError`s
+/// instead of aborting the whole read.
+pub fn parse_people<R: Read>(input: R) -> Result<(Vec<PersonIn>, Vec<RowError>), ImportError> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(input);
+
+    let mut positions: [usize; 5] = [0, 1, 2, 3, 4];
+    let mut seen_emails: HashMap<String, u64> = HashMap::new();
+    let mut people: Vec<PersonIn> = Vec::new();
+    let mut errors: Vec<RowError> = Vec::new();
+
+    for (index, record_result) in rdr.records().enumerate() {
+        let record = match record_result {
+            Ok(record) => record,
+            Err(error) => {
+                let line = error.position().map(|p| p.line()).unwrap_or(0);
+                errors.push(RowError::new(line, "", "", &error.to_string()));
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+
+        if index == 0 && is_header(&record) {
+            positions = map_header(&record)?;
+            continue;
+        }
+
+        match parse_row(&record, &positions, line) {
+            Ok(person) => match seen_emails.get(&person.email) {
+                Some(first_line) => errors.push(RowError::new(
+                    line,
+                    "email",
+                    &person.email,
+                    &format!("duplicated, first seen on line {}", first_line),
+                )),
+                None => {
+                    seen_emails.insert(person.email.clone(), line);
+                    people.push(person);
+                }
+            },
+            Err(row_errors) => errors.extend(row_errors),
+        }
+    }
+
+    Ok((people, errors))
+}
+
+fn is_header(record: &StringRecord) -> bool {
+    record.iter().any(|field| field.to_lowercase() == "email")
+}
+
+fn map_header(record: &StringRecord) -> Result<[usize; 5], ImportError> {
+    let mut positions: [usize; 5] = [0; 5];
+
+    for (position, column) in positions.iter_mut().zip(COLUMNS.iter()) {
+        *position = match record
+            .iter()
+            .position(|field| field.to_lowercase() == *column)
+        {
+            Some(index) => index,
+            None => return Err(ImportError::MissingColumn(column.to_string())),
+        };
+    }
+
+    Ok(positions)
+}
+
+fn parse_row(
+    record: &StringRecord,
+    positions: &[usize; 5],
+    line: u64,
+) -> Result<PersonIn, Vec<RowError>> {
+    let mut errors: Vec<RowError> = Vec::new();
+    let field = |index: usize| record.get(positions[index]).unwrap_or("").to_string();
+
+    let expected = positions.iter().max().unwrap_or(&0) + 1;
+    if record.len() < expected {
+        return Err(vec![RowError::new(
+            line,
+            "",
+            "",
+            &format!("expected {} columns, found {}", expected, record.len()),
+        )]);
+    }
+
+    let person = PersonIn {
+        name: field(0),
+        dept: field(1),
+        role: field(2),
+        email: field(3),
+        currency: field(4).to_uppercase(),
+    };
+
+    for (column, value) in [
+        ("name", &person.name),
+        ("dept", &person.dept),
+        ("role", &person.role),
+    ] {
+        if value.is_empty() {
+            errors.push(RowError::new(line, column, value, "must not be empty"));
+        }
+    }
+    if email_validator(&person.email).is_err() {
+        errors.push(RowError::new(line, "email", &person.email, "invalid email"));
+    }
+    if !is_currency_code(&person.currency) {
+        errors.push(RowError::new(
+            line,
+            "currency",
+            &person.currency,
+            "not an ISO 4217 code",
+        ));
+    }
+
+    if errors.is_empty() {
+        Ok(person)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::utils::import::{parse_people, ImportError};
+
+    #[rstest]
+    fn parse_people_without_header() {
+        let input = "Jim Halpert,Sales,Salesman,jim@dm.com,USD\n\
+                     Dwight Schrute,Sales,Manager,dwight@dm.com,eur\n";
+
+        let (people, errors) = parse_people(input.as_bytes()).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(people.len(), 2);
+        assert_eq!(people[1].name, "Dwight Schrute");
+        assert_eq!(people[1].currency, "EUR");
+    }
+
+    #[rstest]
+    fn parse_people_with_header_in_any_order() {
+        let input = "Email, Name, Currency, Role, Dept\n\
+                     jim@dm.com, Jim Halpert, USD, Salesman, Sales\n";
+
+        let (people, errors) = parse_people(input.as_bytes()).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(people.len(), 1);
+        assert_eq!(people[0].email, "jim@dm.com");
+        assert_eq!(people[0].dept, "Sales");
+    }
+
+    #[rstest]
+    fn parse_people_header_missing_column() {
+        let input = "name,email,currency\nJim Halpert,jim@dm.com,USD\n";
+
+        let result = parse_people(input.as_bytes());
+
+        assert!(matches!(result, Err(ImportError::MissingColumn(column)) if column == "dept"));
+    }
+
+    #[rstest]
+    fn parse_people_collects_row_errors() {
+        let input = "Jim Halpert,Sales,Salesman,jim@dm.com,USD\n\
+                     Pam Beesly,,Receptionist,pam@dm,U$D\n\
+                     Too,Short\n\
+                     Jim Again,Sales,Salesman,jim@dm.com,USD\n";
+
+        let (people, errors) = parse_people(input.as_bytes()).unwrap();
+
+        assert_eq!(people.len(), 1);
+        assert_eq!(errors.len(), 5);
+        assert_eq!(errors[0].line, 2);
+        assert_eq!(errors[0].field, "dept");
+        assert_eq!(errors[1].field, "email");
+        assert_eq!(errors[2].field, "currency");
+        assert_eq!(errors[3].line, 3);
+        assert_eq!(errors[4].line, 4);
+    }
+}
@@ -0,0 +1,183 @@
+use std::io;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::utils::cli::print_table;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Ndjson,
+}
+
+/// A row of command output. `fields` and `row` drive the table and CSV output, while
+/// JSON output comes from `Serialize`.
+pub trait Record: Serialize {
+    fn fields() -> Vec<&'static str>;
+    fn row(&self) -> Vec<String>;
+}
+
+/// Writes the sections of a command's output in the format chosen with `--format`.
+///
+/// - `table`: one table per section, with messages in between.
+/// - `json`: a single object keyed by section, written by `finish`.
+/// - `csv`: one block with a header per section, separated by an empty line.
+/// - `ndjson`: one object per record, tagged with its `section`.
+///
+/// Outside of `table`, messages go to stderr so that stdout only holds data.
+pub struct Output {
+    format: OutputFormat,
+    document: Map<String, Value>,
+    csv_blocks: usize,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Self {
+        Output {
+            format,
+            document: Map::new(),
+            csv_blocks: 0,
+        }
+    }
+
+    pub fn message(&self, text: &str) {
+        match self.format {
+            OutputFormat::Table => println!("{}", text),
+            _ => eprintln!("{}", text),
+        }
+    }
+
+    pub fn records<T: Record>(&mut self, section: &str, records: &[T], exclude: &[&str]) {
+        let fields: Vec<&str> = T::fields()
+            .into_iter()
+            .filter(|field| !exclude.contains(field))
+            .collect();
+
+        match self.format {
+            OutputFormat::Table => {
+                let rows = records
+                    .iter()
+                    .map(|record| select_fields(record, exclude))
+                    .collect();
+                print_table(&fields, rows);
+            }
+            OutputFormat::Csv => {
+                if self.csv_blocks > 0 {
+                    println!();
+                }
+                self.csv_blocks += 1;
+
+                let mut writer = csv::Writer::from_writer(io::stdout());
+                writer.write_record(&fields).unwrap();
+                for record in records {
+                    writer.write_record(select_fields(record, exclude)).unwrap();
+                }
+                writer.flush().unwrap();
+            }
+            OutputFormat::Json => {
+                let values = records
+                    .iter()
+                    .map(|record| Value::Object(to_object(record, exclude)))
+                    .collect();
+                self.document
+                    .insert(section.to_string(), Value::Array(values));
+            }
+            OutputFormat::Ndjson => {
+                for record in records {
+                    let mut object = to_object(record, exclude);
+                    object.insert("section".to_string(), Value::from(section));
+                    println!("{}", Value::Object(object));
+                }
+            }
+        }
+    }
+
+    /// Like `records`, for a section that always holds exactly one record. In JSON it is
+    /// written as an object instead of an array.
+    pub fn record<T: Record>(&mut self, section: &str, record: &T, exclude: &[&str]) {
+        match self.format {
+            OutputFormat::Json => {
+                self.document.insert(
+                    section.to_string(),
+                    Value::Object(to_object(record, exclude)),
+                );
+            }
+            _ => self.records(section, std::slice::from_ref(record), exclude),
+        }
+    }
+
+    pub fn finish(self) {
+        if self.format == OutputFormat::Json && !self.document.is_empty() {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&Value::Object(self.document)).unwrap()
+            );
+        }
+    }
+}
+
+fn select_fields<T: Record>(record: &T, exclude: &[&str]) -> Vec<String> {
+    T::fields()
+        .into_iter()
+        .zip(record.row())
+        .filter(|(field, _)| !exclude.contains(field))
+        .map(|(_, value)| value)
+        .collect()
+}
+
+fn to_object<T: Record>(record: &T, exclude: &[&str]) -> Map<String, Value> {
+    match serde_json::to_value(record).unwrap() {
+        Value::Object(mut object) => {
+            object.retain(|field, _| !exclude.contains(&field.as_str()));
+            object
+        }
+        other => {
+            let mut object = Map::new();
+            object.insert("value".to_string(), other);
+            object
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use serde::Serialize;
+
+    use crate::utils::output::{select_fields, to_object, Record};
+
+    #[derive(Serialize)]
+    struct Sample {
+        name: String,
+        points: i64,
+    }
+
+    impl Record for Sample {
+        fn fields() -> Vec<&'static str> {
+            vec!["name", "points"]
+        }
+
+        fn row(&self) -> Vec<String> {
+            vec![self.name.clone(), self.points.to_string()]
+        }
+    }
+
+    #[rstest]
+    fn excluded_fields_are_dropped() {
+        let sample = Sample {
+            name: "Jim".to_string(),
+            points: 10,
+        };
+
+        assert_eq!(select_fields(&sample, &["points"]), vec!["Jim"]);
+        assert_eq!(select_fields(&sample, &[]), vec!["Jim", "10"]);
+
+        let object = to_object(&sample, &["points"]);
+        assert_eq!(object.len(), 1);
+        assert_eq!(object["name"], "Jim");
+    }
+}
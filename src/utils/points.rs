@@ -0,0 +1,122 @@
+use rust_decimal::Decimal;
+use serde::{Serialize, Serializer};
+
+/// Points are stored as integer hundredths of a point, so `12.5` points is `1250`.
+pub const POINTS_SCALE: i64 = 100;
+const POINTS_DECIMALS: usize = 2;
+
+/// Parses a user supplied amount of points such as `10`, `-3.5` or `0.25` into
+/// minor units without going through floating point.
+pub fn parse_points(value: &str) -> Result<i64, String> {
+    let trimmed = value.trim();
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (digits, ""),
+    };
+
+    if (whole.is_empty() && fraction.is_empty())
+        || !whole.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("`{}` is not a valid amount of points", value));
+    }
+    if fraction.len() > POINTS_DECIMALS {
+        return Err(format!(
+            "`{}` has more than {} decimal places",
+            value, POINTS_DECIMALS
+        ));
+    }
+
+    let whole_units = match whole {
+        "" => 0,
+        _ => whole
+            .parse::<i64>()
+            .map_err(|_| format!("`{}` is too large", value))?,
+    };
+    let fraction_units = format!("{:0<width$}", fraction, width = POINTS_DECIMALS)
+        .parse::<i64>()
+        .unwrap_or(0);
+
+    let units = whole_units
+        .checked_mul(POINTS_SCALE)
+        .and_then(|units| units.checked_add(fraction_units))
+        .ok_or(format!("`{}` is too large", value))?;
+
+    if negative {
+        Ok(-units)
+    } else {
+        Ok(units)
+    }
+}
+
+pub fn format_points(value: i64) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let absolute = value.unsigned_abs();
+    let scale = POINTS_SCALE as u64;
+
+    format!(
+        "{}{}.{:0width$}",
+        sign,
+        absolute / scale,
+        absolute % scale,
+        width = POINTS_DECIMALS
+    )
+}
+
+/// Converts minor units into an exact decimal amount of points, e.g. `1250` into `12.50`.
+pub fn points_to_decimal(value: i64) -> Decimal {
+    Decimal::new(value, POINTS_DECIMALS as u32)
+}
+
+/// Serializes minor units as a decimal amount of points, for machine-readable output.
+pub fn serialize_points<S: Serializer>(value: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    Serialize::serialize(&points_to_decimal(*value), serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::utils::points::{format_points, parse_points, points_to_decimal};
+
+    #[rstest]
+    #[case("10", 1000)]
+    #[case("0.1", 10)]
+    #[case("12.34", 1234)]
+    #[case("-3.5", -350)]
+    #[case("+7", 700)]
+    #[case(".5", 50)]
+    fn positive_parse_points(#[case] input: &str, #[case] expected: i64) {
+        assert_eq!(parse_points(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("abc")]
+    #[case("1.234")]
+    #[case("1,5")]
+    #[case("99999999999999999999")]
+    fn negative_parse_points(#[case] input: &str) {
+        assert!(parse_points(input).is_err());
+    }
+
+    #[rstest]
+    #[case(1000, "10.00")]
+    #[case(5, "0.05")]
+    #[case(-350, "-3.50")]
+    #[case(0, "0.00")]
+    fn format_points_output(#[case] input: i64, #[case] expected: &str) {
+        assert_eq!(format_points(input), expected);
+    }
+
+    #[rstest]
+    #[case(1250, "12.50")]
+    #[case(-5, "-0.05")]
+    fn points_to_decimal_output(#[case] input: i64, #[case] expected: &str) {
+        assert_eq!(points_to_decimal(input).to_string(), expected);
+    }
+}
@@ -1,5 +1,7 @@
 use passwords::PasswordGenerator;
 
+use crate::config::PasswordPolicy;
+
 pub fn generate_simple_password(size: usize) -> String {
     let pg = PasswordGenerator {
         length: size,
@@ -15,11 +17,60 @@ pub fn generate_simple_password(size: usize) -> String {
     pg.generate_one().unwrap()
 }
 
+/// Generates a password meant to be read and typed by a person: no spaces, no
+/// symbols and no look-alike characters such as `l` and `1`.
+pub fn generate_temporary_password(size: usize) -> String {
+    let pg = PasswordGenerator {
+        length: size,
+        numbers: true,
+        lowercase_letters: true,
+        uppercase_letters: true,
+        symbols: false,
+        spaces: false,
+        exclude_similar_characters: true,
+        strict: true,
+    };
+
+    pg.generate_one().unwrap()
+}
+
+pub fn check_password_strength(password: &str, policy: &PasswordPolicy) -> Result<(), Vec<String>> {
+    let mut violations: Vec<String> = Vec::new();
+
+    if password.chars().count() < policy.min_length {
+        violations.push(format!(
+            "must be at least {} characters long",
+            policy.min_length
+        ));
+    }
+    if policy.require_numbers && !password.chars().any(|c| c.is_ascii_digit()) {
+        violations.push("must contain a number".to_string());
+    }
+    if policy.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+        violations.push("must contain a lowercase letter".to_string());
+    }
+    if policy.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+        violations.push("must contain an uppercase letter".to_string());
+    }
+    if policy.require_symbols && password.chars().all(|c| c.is_alphanumeric()) {
+        violations.push("must contain a symbol".to_string());
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
 
-    use crate::utils::user::generate_simple_password;
+    use crate::config::PasswordPolicy;
+    use crate::utils::user::{
+        check_password_strength, generate_simple_password, generate_temporary_password,
+    };
 
     #[rstest]
     #[case(10_usize)]
@@ -29,4 +80,41 @@ mod tests {
         let pwd = generate_simple_password(input);
         assert_eq!(pwd.chars().count(), input);
     }
+
+    #[rstest]
+    fn generate_temporary_password_satisfies_default_policy() {
+        let pwd = generate_temporary_password(12_usize);
+
+        assert_eq!(pwd.chars().count(), 12);
+        assert!(pwd.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert!(check_password_strength(&pwd, &PasswordPolicy::default()).is_ok());
+    }
+
+    #[rstest]
+    #[case("Dunder2023")]
+    #[case("Scr4ntonPaper")]
+    fn positive_check_password_strength(#[case] input: &str) {
+        assert!(check_password_strength(input, &PasswordPolicy::default()).is_ok());
+    }
+
+    #[rstest]
+    #[case("short1A", 1)]
+    #[case("nodigitsHere", 1)]
+    #[case("alllowercase", 2)]
+    #[case("abc", 3)]
+    fn negative_check_password_strength(#[case] input: &str, #[case] violations: usize) {
+        let result = check_password_strength(input, &PasswordPolicy::default());
+        assert_eq!(result.unwrap_err().len(), violations);
+    }
+
+    #[rstest]
+    fn check_password_strength_requires_symbols() {
+        let policy = PasswordPolicy {
+            require_symbols: true,
+            ..PasswordPolicy::default()
+        };
+
+        assert!(check_password_strength("Dunder2023", &policy).is_err());
+        assert!(check_password_strength("Dunder-2023", &policy).is_ok());
+    }
 }